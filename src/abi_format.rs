@@ -0,0 +1,42 @@
+use ethabi::Token;
+use ethereum_types::U256;
+
+// Renders a token the way it would be written as input, so decoded values
+// can be copied straight back into a prompt.
+pub fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format!("{:?}", address),
+        Token::Uint(value) => value.to_string(),
+        Token::Int(value) => format_int(*value),
+        Token::Bool(value) => value.to_string(),
+        Token::String(value) => format!("{:?}", value),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        Token::Array(items) | Token::FixedArray(items) => {
+            format!("[{}]", items.iter().map(format_token).collect::<Vec<_>>().join(", "))
+        },
+        Token::Tuple(items) => {
+            format!("({})", items.iter().map(format_token).collect::<Vec<_>>().join(", "))
+        },
+    }
+}
+
+// Signed values come back from the ABI decoder as 256 bit two's complement
+fn format_int(value: U256) -> String {
+    if value.bit(255) {
+        format!("-{}", (!value).overflowing_add(U256::one()).0)
+    } else {
+        value.to_string()
+    }
+}
+
+// Wei as ether with the trailing zeros of the fraction dropped, e.g. "1.5 ether"
+pub fn format_ether(wei: U256) -> String {
+    let unit = U256::exp10(18);
+    let fraction = format!("{:018}", (wei % unit).as_u64());
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{} ether", wei / unit)
+    } else {
+        format!("{}.{} ether", wei / unit, fraction)
+    }
+}
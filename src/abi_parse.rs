@@ -0,0 +1,443 @@
+use ethabi::param_type::ParamType;
+use ethabi::Token;
+use ethereum_types::{H160, U256};
+
+use std::io;
+
+// Turns user supplied text into an ethabi::Token for the given parameter type.
+// Compound values use JSON-like literals: arrays as [1, 2, 3], tuples as (0xabc, "hi")
+// and both can be nested, e.g. [(1, [true, false]), (2, [])].
+pub fn parse_token(param: &ParamType, input: &str) -> Result<Token, io::Error> {
+    let input = input.trim();
+    match param {
+        ParamType::Address => {
+            let parsed_address = strip_hex_prefix(input).parse::<H160>()
+                .map_err(|e| invalid_input(format!("Invalid address '{}': {}", input, e)))?;
+            Ok(Token::Address(parsed_address))
+        },
+        ParamType::Uint(bits) => Ok(Token::Uint(parse_uint(input, *bits)?)),
+        ParamType::Int(bits) => Ok(Token::Int(parse_int(input, *bits)?)),
+        ParamType::String => Ok(Token::String(unquote(input)?)),
+        ParamType::Bool => {
+            let value = match input {
+                "true" => true,
+                "false" => false,
+                _ => return Err(invalid_input(format!("Invalid bool '{}', expected true or false", input))),
+            };
+            Ok(Token::Bool(value))
+        },
+        ParamType::Bytes => Ok(Token::Bytes(parse_bytes(input)?)),
+        ParamType::FixedBytes(size) => {
+            if !(1..=32).contains(size) {
+                return Err(invalid_input(format!("bytes{} is not a valid type, expected bytes1 to bytes32", size)));
+            }
+            let mut bytes = parse_bytes(input)?;
+            if bytes.len() > *size {
+                return Err(invalid_input(format!("bytes{} takes at most {} bytes, got {}", size, size, bytes.len())));
+            }
+            // Like Solidity's bytes4("ab") or bytes4(hex"ab"), shorter values are
+            // left aligned and padded with zeros on the right
+            bytes.resize(*size, 0);
+            Ok(Token::FixedBytes(bytes))
+        },
+        ParamType::Array(inner) => {
+            let items = split_list(input, '[', ']')?;
+            let tokens = items.iter()
+                .map(|item| parse_token(inner, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Token::Array(tokens))
+        },
+        ParamType::FixedArray(inner, size) => {
+            let items = split_list(input, '[', ']')?;
+            if items.len() != *size {
+                return Err(invalid_input(format!("{} expects exactly {} elements, got {}", param, size, items.len())));
+            }
+            let tokens = items.iter()
+                .map(|item| parse_token(inner, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Token::FixedArray(tokens))
+        },
+        ParamType::Tuple(components) => {
+            // Accept both (a, b) and [a, b] for tuples, structs are often written as lists
+            let items = if input.starts_with('[') {
+                split_list(input, '[', ']')?
+            } else {
+                split_list(input, '(', ')')?
+            };
+            if items.len() != components.len() {
+                return Err(invalid_input(format!("{} expects {} components, got {}", param, components.len(), items.len())));
+            }
+            let tokens = components.iter().zip(items.iter())
+                .map(|(component, item)| parse_token(component, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Token::Tuple(tokens))
+        },
+    }
+}
+
+// Parses a wei amount, accepting units like "1 ether"
+pub fn parse_value(value: Option<&str>) -> Result<U256, io::Error> {
+    match value {
+        Some(value) => match parse_token(&ParamType::Uint(256), value)? {
+            Token::Uint(value) => Ok(value),
+            _ => unreachable!("uint params always parse to uint tokens"),
+        },
+        None => Ok(U256::zero()),
+    }
+}
+
+// Literal used when the user just presses enter at a prompt
+pub fn default_value(param: &ParamType, default_address: H160) -> String {
+    match param {
+        ParamType::Address => format!("{:?}", default_address),
+        ParamType::Uint(_) | ParamType::Int(_) => "0".to_string(),
+        ParamType::String => "".to_string(),
+        ParamType::Bool => "false".to_string(),
+        ParamType::Bytes => "0x".to_string(),
+        ParamType::FixedBytes(size) => format!("0x{}", "00".repeat(*size)),
+        ParamType::Array(_) => "[]".to_string(),
+        ParamType::FixedArray(inner, size) => {
+            let items = vec![quote_if_string(inner, default_value(inner, default_address)); *size];
+            format!("[{}]", items.join(", "))
+        },
+        ParamType::Tuple(components) => {
+            let items = components.iter()
+                .map(|component| quote_if_string(component, default_value(component, default_address)))
+                .collect::<Vec<_>>();
+            format!("({})", items.join(", "))
+        },
+    }
+}
+
+fn quote_if_string(param: &ParamType, value: String) -> String {
+    if *param == ParamType::String {
+        format!("{:?}", value)
+    } else {
+        value
+    }
+}
+
+// Ether denominations accepted after a number, e.g. "5 gwei" or "1.5ether"
+const UNITS: &[(&str, usize)] = &[
+    ("wei", 0),
+    ("kwei", 3),
+    ("mwei", 6),
+    ("gwei", 9),
+    ("szabo", 12),
+    ("finney", 15),
+    ("ether", 18),
+];
+
+fn parse_uint(input: &str, bits: usize) -> Result<U256, io::Error> {
+    if input.starts_with('-') {
+        return Err(invalid_input(format!("uint{} cannot be negative, got '{}'", bits, input)));
+    }
+    let value = parse_magnitude(input.strip_prefix('+').unwrap_or(input))?;
+    if bits < 256 && value >> bits != U256::zero() {
+        return Err(invalid_input(format!(
+            "Value {} does not fit in uint{} (max {})", input, bits, (U256::one() << bits) - 1)));
+    }
+    Ok(value)
+}
+
+// Signed integers are encoded as 256 bit two's complement
+fn parse_int(input: &str, bits: usize) -> Result<U256, io::Error> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, input.strip_prefix('+').unwrap_or(input)),
+    };
+    let magnitude = parse_magnitude(digits)?;
+
+    // int{bits} covers -2^(bits-1) ..= 2^(bits-1) - 1
+    let limit = U256::one() << (bits - 1);
+    if negative && magnitude > limit {
+        return Err(invalid_input(format!("Value {} does not fit in int{} (min -{})", input, bits, limit)));
+    }
+    if !negative && magnitude >= limit {
+        return Err(invalid_input(format!("Value {} does not fit in int{} (max {})", input, bits, limit - 1)));
+    }
+
+    if negative && !magnitude.is_zero() {
+        Ok((!magnitude).overflowing_add(U256::one()).0)
+    } else {
+        Ok(magnitude)
+    }
+}
+
+// Parses an unsigned literal: hex (0xff), decimal (1_000), scientific (1.5e18)
+// or a decimal followed by an ether unit (1 ether, 5 gwei, 0.1ether).
+fn parse_magnitude(input: &str) -> Result<U256, io::Error> {
+    let invalid = |reason: &str| invalid_input(format!("Invalid integer '{}': {}", input, reason));
+    let literal = input.replace('_', "");
+
+    if let Some(hex) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        if hex.is_empty() || hex.len() > 64 {
+            return Err(invalid("hex literal must have between 1 and 64 digits"));
+        }
+        return U256::from_str_radix(hex, 16).map_err(|e| invalid(&e.to_string()));
+    }
+
+    // Split off a trailing unit, if any
+    let lowercase = literal.to_lowercase();
+    let (number, unit_exponent) = UNITS.iter()
+        .filter_map(|(unit, exponent)| lowercase.strip_suffix(unit).map(|number| (number.trim_end(), *exponent)))
+        // "gwei" also ends with "wei", so prefer the longest unit match
+        .min_by_key(|(number, _)| number.len())
+        .unwrap_or((lowercase.as_str(), 0));
+
+    // Split off a scientific exponent, if any
+    let (mantissa, exponent) = match number.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: usize = exponent.parse().map_err(|_| invalid("bad exponent"))?;
+            (mantissa, exponent)
+        },
+        None => (number, 0),
+    };
+
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(invalid("no digits"));
+    }
+    if !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid("expected decimal digits, a 0x hex value or a number followed by a unit"));
+    }
+
+    // Shift the decimal point right by the total exponent, the result must be whole
+    let scale = exponent.saturating_add(unit_exponent);
+    let fraction = fraction.trim_end_matches('0');
+    if integer.trim_start_matches('0').is_empty() && fraction.is_empty() {
+        return Ok(U256::zero());
+    }
+    // 2^256 has 78 decimal digits, anything scaled further cannot fit
+    if scale > 78 {
+        return Err(invalid("value exceeds 256 bits"));
+    }
+    if fraction.len() > scale {
+        return Err(invalid("value is not a whole number"));
+    }
+    let digits = format!("{}{}{}", integer, fraction, "0".repeat(scale - fraction.len()));
+    let digits = digits.trim_start_matches('0');
+    U256::from_dec_str(digits).map_err(|_| invalid("value exceeds 256 bits"))
+}
+
+// Byte values are hex with or without 0x, or a double quoted string for its UTF-8 bytes
+fn parse_bytes(input: &str) -> Result<Vec<u8>, io::Error> {
+    if input.len() >= 2 && input.starts_with('"') && input.ends_with('"') {
+        return Ok(unquote(input)?.into_bytes());
+    }
+
+    let digits = strip_hex_prefix(input);
+    if digits.len() % 2 == 1 {
+        return Err(invalid_input(format!(
+            "Hex value '{}' has an odd number of digits, each byte needs two (e.g. 0x0{})", input, digits)));
+    }
+    hex::decode(digits).map_err(|e| invalid_input(format!(
+        "Invalid bytes '{}': {}. Use hex like 0xdeadbeef or a quoted string like \"hello\"", input, e)))
+}
+
+fn strip_hex_prefix(input: &str) -> &str {
+    input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input)
+}
+
+// Strings may be written bare or double quoted, quoting is only needed inside
+// arrays and tuples when the string contains a comma or bracket.
+fn unquote(input: &str) -> Result<String, io::Error> {
+    if input.len() < 2 || !input.starts_with('"') || !input.ends_with('"') {
+        return Ok(input.to_string());
+    }
+
+    let mut result = String::new();
+    let mut chars = input[1..input.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(escaped @ ('"' | '\\')) => result.push(escaped),
+            Some(other) => return Err(invalid_input(format!("Unknown escape sequence '\\{}' in {}", other, input))),
+            None => return Err(invalid_input(format!("Unterminated escape sequence in {}", input))),
+        }
+    }
+    Ok(result)
+}
+
+// Splits "[a, (b, c), "d,e"]" into its top level items, respecting nesting and quotes
+fn split_list(input: &str, open: char, close: char) -> Result<Vec<&str>, io::Error> {
+    let inner = input.strip_prefix(open)
+        .and_then(|rest| rest.strip_suffix(close))
+        .ok_or_else(|| invalid_input(format!("Expected a value wrapped in {}{}, got '{}'", open, close, input)))?;
+
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (pos, c) in inner.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {},
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '(' => depth += 1,
+            ']' | ')' => {
+                depth = depth.checked_sub(1)
+                    .ok_or_else(|| invalid_input(format!("Unbalanced brackets in '{}'", input)))?;
+            },
+            ',' if depth == 0 => {
+                items.push(inner[start..pos].trim());
+                start = pos + 1;
+            },
+            _ => {},
+        }
+    }
+
+    if in_string {
+        return Err(invalid_input(format!("Unterminated string in '{}'", input)));
+    }
+    if depth != 0 {
+        return Err(invalid_input(format!("Unbalanced brackets in '{}'", input)));
+    }
+    items.push(inner[start..].trim());
+
+    if items.iter().any(|item| item.is_empty()) {
+        return Err(invalid_input(format!("Empty element in '{}'", input)));
+    }
+    Ok(items)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(param: &ParamType, input: &str) -> Token {
+        parse_token(param, input).unwrap()
+    }
+
+    fn error(param: &ParamType, input: &str) -> String {
+        parse_token(param, input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_integers_in_every_notation() {
+        let uint = ParamType::Uint(256);
+        assert_eq!(parse(&uint, "1_000"), Token::Uint(U256::from(1000)));
+        assert_eq!(parse(&uint, "0xff"), Token::Uint(U256::from(255)));
+        assert_eq!(parse(&uint, "1.5e3"), Token::Uint(U256::from(1500)));
+        assert_eq!(parse(&uint, "5 gwei"), Token::Uint(U256::from(5_000_000_000u64)));
+        assert_eq!(parse(&uint, "0.1ether"), Token::Uint(U256::from(100_000_000_000_000_000u64)));
+        assert_eq!(parse(&uint, "2 wei"), Token::Uint(U256::from(2)));
+    }
+
+    #[test]
+    fn rejects_fractions_and_garbage() {
+        let uint = ParamType::Uint(256);
+        assert!(error(&uint, "1.5 wei").contains("not a whole number"));
+        assert!(error(&uint, "12abc").contains("expected decimal digits"));
+        assert!(error(&uint, "-1").contains("cannot be negative"));
+        assert!(error(&uint, "0x").contains("between 1 and 64 digits"));
+    }
+
+    #[test]
+    fn checks_bit_widths() {
+        assert_eq!(parse(&ParamType::Uint(8), "255"), Token::Uint(U256::from(255)));
+        assert!(error(&ParamType::Uint(8), "256").contains("does not fit in uint8 (max 255)"));
+        assert!(error(&ParamType::Int(8), "128").contains("does not fit in int8 (max 127)"));
+        assert!(error(&ParamType::Int(8), "-129").contains("does not fit in int8 (min -128)"));
+    }
+
+    #[test]
+    fn overflowing_values_are_rejected() {
+        let uint = ParamType::Uint(256);
+        let max = U256::MAX.to_string();
+        assert_eq!(parse(&uint, &max), Token::Uint(U256::MAX));
+        // One more than 2^256 - 1
+        let too_big = "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+        assert!(error(&uint, too_big).contains("exceeds 256 bits"));
+        assert!(error(&uint, "1e79").contains("exceeds 256 bits"));
+        assert!(error(&uint, &format!("0x1{}", "0".repeat(64))).contains("between 1 and 64 digits"));
+    }
+
+    #[test]
+    fn negative_ints_are_twos_complement() {
+        assert_eq!(parse(&ParamType::Int(256), "-1"), Token::Int(U256::MAX));
+        assert_eq!(parse(&ParamType::Int(8), "-128"), Token::Int(U256::MAX - 127));
+        assert_eq!(parse(&ParamType::Int(256), "-0"), Token::Int(U256::zero()));
+        assert_eq!(parse(&ParamType::Int(16), "+42"), Token::Int(U256::from(42)));
+    }
+
+    #[test]
+    fn parses_bytes_as_hex_or_quoted_text() {
+        assert_eq!(parse(&ParamType::Bytes, "0xdead"), Token::Bytes(vec![0xde, 0xad]));
+        assert_eq!(parse(&ParamType::Bytes, "\"hi\""), Token::Bytes(b"hi".to_vec()));
+        assert_eq!(parse(&ParamType::FixedBytes(4), "0xab"), Token::FixedBytes(vec![0xab, 0, 0, 0]));
+        assert!(error(&ParamType::Bytes, "0xabc").contains("odd number of digits"));
+        assert!(error(&ParamType::FixedBytes(2), "0xaabbcc").contains("at most 2 bytes"));
+    }
+
+    #[test]
+    fn parses_nested_arrays_and_tuples() {
+        let param = ParamType::Array(Box::new(ParamType::Tuple(vec![
+            ParamType::Uint(256),
+            ParamType::Array(Box::new(ParamType::Bool)),
+        ])));
+        assert_eq!(parse(&param, "[(1, [true, false]), (2, [])]"), Token::Array(vec![
+            Token::Tuple(vec![Token::Uint(U256::one()), Token::Array(vec![Token::Bool(true), Token::Bool(false)])]),
+            Token::Tuple(vec![Token::Uint(U256::from(2)), Token::Array(vec![])]),
+        ]));
+        // Structs may also be written as lists
+        let tuple = ParamType::Tuple(vec![ParamType::String, ParamType::Bool]);
+        assert_eq!(parse(&tuple, "[\"a, b\", true]"), Token::Tuple(vec![Token::String("a, b".to_string()), Token::Bool(true)]));
+    }
+
+    #[test]
+    fn fixed_arrays_and_tuples_check_their_length() {
+        let fixed = ParamType::FixedArray(Box::new(ParamType::Uint(8)), 2);
+        assert!(error(&fixed, "[1, 2, 3]").contains("expects exactly 2 elements, got 3"));
+        let tuple = ParamType::Tuple(vec![ParamType::Bool, ParamType::Bool]);
+        assert!(error(&tuple, "(true)").contains("expects 2 components, got 1"));
+    }
+
+    #[test]
+    fn malformed_lists_are_reported() {
+        let param = ParamType::Array(Box::new(ParamType::Array(Box::new(ParamType::Uint(8)))));
+        assert!(error(&param, "[[1, 2], [3]").contains("Unbalanced brackets"));
+        assert!(error(&param, "[[1, 2]], [3]]").contains("Unbalanced brackets"));
+        assert!(error(&param, "[1, 2").contains("Expected a value wrapped in []"));
+        assert!(error(&param, "[[1], , [2]]").contains("Empty element"));
+        let strings = ParamType::Array(Box::new(ParamType::String));
+        assert!(error(&strings, "[\"abc]").contains("Unterminated string"));
+    }
+
+    #[test]
+    fn strings_unescape_when_quoted() {
+        assert_eq!(parse(&ParamType::String, "plain text"), Token::String("plain text".to_string()));
+        assert_eq!(parse(&ParamType::String, "\"a\\\"b\\n\""), Token::String("a\"b\n".to_string()));
+        assert!(error(&ParamType::String, "\"\\q\"").contains("Unknown escape sequence"));
+    }
+
+    #[test]
+    fn addresses_and_bools() {
+        let address = "0x00000000000000000000000000000000000000aa";
+        assert_eq!(parse(&ParamType::Address, address), Token::Address(H160::from_low_u64_be(0xaa)));
+        assert!(error(&ParamType::Address, "0x12").contains("Invalid address"));
+        assert!(error(&ParamType::Bool, "yes").contains("expected true or false"));
+    }
+}
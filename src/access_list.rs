@@ -0,0 +1,57 @@
+use ethereum_types::{H160, H256};
+
+use std::io;
+
+use crate::{abi_parse, ContractsData};
+
+// EIP-2930 addresses with the storage slots of each, warmed before the transaction runs
+pub type AccessList = Vec<(H160, Vec<H256>)>;
+
+// A storage slot as a number, like 0 for the first state variable, or as a 32 byte hex key
+pub fn parse_storage_key(input: &str) -> Result<H256, io::Error> {
+    let value = abi_parse::parse_value(Some(input.trim()))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid storage key {}: {}", input, e)))?;
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    Ok(H256(bytes))
+}
+
+// Adds an address and its slots, merged into the entry of the address if it has one
+pub fn add(access_list: &mut AccessList, address: H160, keys: Vec<H256>) {
+    let index = match access_list.iter().position(|(entry, _)| *entry == address) {
+        Some(index) => index,
+        None => {
+            access_list.push((address, Vec::new()));
+            access_list.len() - 1
+        },
+    };
+    let entry_keys = &mut access_list[index].1;
+    for key in keys {
+        if !entry_keys.contains(&key) {
+            entry_keys.push(key);
+        }
+    }
+}
+
+// Number of addresses and of storage keys, which is what the list costs
+pub fn size(access_list: &[(H160, Vec<H256>)]) -> (usize, usize) {
+    (access_list.len(), access_list.iter().map(|(_, keys)| keys.len()).sum())
+}
+
+pub fn print_access_list(access_list: &[(H160, Vec<H256>)], contracts: &ContractsData) {
+    if access_list.is_empty() {
+        println!("Access list is empty");
+        return;
+    }
+    let (addresses, keys) = size(access_list);
+    println!("Access list, {} address(es) and {} storage key(s):", addresses, keys);
+    for (address, keys) in access_list {
+        match contracts.iter().find(|(_, contract_data)| contract_data.address == Some(*address)) {
+            Some((name, _)) => println!("  {:?} ({})", address, name),
+            None => println!("  {:?}", address),
+        }
+        for key in keys {
+            println!("    slot {:?}", key);
+        }
+    }
+}
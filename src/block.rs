@@ -0,0 +1,131 @@
+use ethereum_types::{H160, H256, U256};
+use evm::backend::MemoryVicinity;
+use rlp::RlpStream;
+use sha3::{Digest, Keccak256};
+
+use std::io;
+
+use crate::abi_parse;
+
+// A sealed block. Blocks only carry what the playground tracks, so their hashes
+// are stable within a session but don't match those of a real chain.
+pub struct Block {
+    pub number: U256,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub timestamp: U256,
+    pub coinbase: H160,
+    pub base_fee: U256,
+    pub prevrandao: Option<H256>,
+    pub gas_limit: U256,
+    pub gas_used: u64,
+    // Hashes of the transactions in the block, in order
+    pub transactions: Vec<H256>,
+}
+
+impl Block {
+    // Seals the block the vicinity describes
+    pub fn seal(parent_hash: H256, vicinity: &MemoryVicinity, gas_used: u64, transactions: Vec<H256>) -> Self {
+        let mut stream = RlpStream::new_list(9);
+        stream.append(&parent_hash);
+        stream.append(&vicinity.block_number);
+        stream.append(&vicinity.block_timestamp);
+        stream.append(&vicinity.block_coinbase);
+        stream.append(&vicinity.block_base_fee_per_gas);
+        stream.append(&vicinity.block_randomness.unwrap_or_default());
+        stream.append(&vicinity.block_gas_limit);
+        stream.append(&gas_used);
+        stream.append_list(&transactions);
+        let hash = H256::from_slice(&Keccak256::digest(stream.out()));
+
+        Block {
+            number: vicinity.block_number,
+            hash,
+            parent_hash,
+            timestamp: vicinity.block_timestamp,
+            coinbase: vicinity.block_coinbase,
+            base_fee: vicinity.block_base_fee_per_gas,
+            prevrandao: vicinity.block_randomness,
+            gas_limit: vicinity.block_gas_limit,
+            gas_used,
+            transactions,
+        }
+    }
+}
+
+// EIP-1559: the base fee moves by up to 1/8 towards keeping blocks half full.
+// A zero base fee stays zero so gas-free sessions stay gas-free.
+pub fn next_base_fee(base_fee: U256, gas_used: u64, gas_limit: U256) -> U256 {
+    let target = gas_limit / 2;
+    let gas_used = U256::from(gas_used);
+    if base_fee.is_zero() || target.is_zero() || gas_used == target {
+        base_fee
+    } else if gas_used > target {
+        let delta = base_fee * (gas_used - target) / target / 8;
+        base_fee + delta.max(U256::one())
+    } else {
+        base_fee - base_fee * (target - gas_used) / target / 8
+    }
+}
+
+// PREVRANDAO given as a number, in decimal or hex
+pub fn parse_prevrandao(input: &str) -> Result<H256, io::Error> {
+    let value = abi_parse::parse_value(Some(input))?;
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    Ok(H256(bytes))
+}
+
+// Parses a timestamp in seconds, or an offset from `now` like "+3600"
+pub fn parse_timestamp(input: &str, now: U256) -> Result<U256, io::Error> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid timestamp {}: {}", input, e));
+    match input.strip_prefix('+') {
+        Some(offset) => Ok(now + offset.parse::<u64>().map_err(invalid)?),
+        None => Ok(U256::from(input.parse::<u64>().map_err(invalid)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u64 = 1_000_000_000;
+    const GAS_LIMIT: u64 = 30_000_000;
+
+    fn next(base_fee: u64, gas_used: u64) -> U256 {
+        next_base_fee(U256::from(base_fee), gas_used, U256::from(GAS_LIMIT))
+    }
+
+    #[test]
+    fn base_fee_moves_by_at_most_an_eighth() {
+        // Empty blocks lower it by 12.5%, full ones raise it by 12.5%
+        assert_eq!(next(GWEI, 0), U256::from(875_000_000));
+        assert_eq!(next(GWEI, GAS_LIMIT), U256::from(1_125_000_000));
+        // At the target of half the limit it stays put
+        assert_eq!(next(GWEI, GAS_LIMIT / 2), U256::from(GWEI));
+        // Part of the way to the limit moves it part of the way
+        assert_eq!(next(GWEI, GAS_LIMIT * 3 / 4), U256::from(1_062_500_000));
+        for gas_used in (0..=GAS_LIMIT).step_by(1_000_000) {
+            let base_fee = next(GWEI, gas_used);
+            assert!(base_fee >= U256::from(875_000_000) && base_fee <= U256::from(1_125_000_000));
+        }
+    }
+
+    #[test]
+    fn base_fee_edge_cases() {
+        // A block just above the target raises it by at least 1 wei
+        assert_eq!(next(8, GAS_LIMIT / 2 + 1), U256::from(9));
+        assert_eq!(next(0, GAS_LIMIT), U256::zero());
+        assert_eq!(next_base_fee(U256::from(GWEI), 0, U256::zero()), U256::from(GWEI));
+    }
+
+    #[test]
+    fn timestamps_are_absolute_or_relative() {
+        let now = U256::from(1_700_000_000u64);
+        assert_eq!(parse_timestamp("1800000000", now).unwrap(), U256::from(1_800_000_000u64));
+        assert_eq!(parse_timestamp("+3600", now).unwrap(), U256::from(1_700_003_600u64));
+        assert_eq!(parse_timestamp("+0", now).unwrap(), now);
+        assert!(parse_timestamp("-5", now).unwrap_err().to_string().starts_with("invalid timestamp -5"));
+        assert!(parse_timestamp("+1h", now).is_err());
+    }
+}
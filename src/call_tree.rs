@@ -0,0 +1,264 @@
+use ethabi::Token;
+use ethereum_types::{H160, U256};
+use evm::{CreateScheme, ExitReason};
+use serde_json::{json, Map, Value};
+
+use std::fs;
+use std::io;
+
+use crate::abi_format::format_token;
+use crate::revert::decode_revert;
+use crate::ContractsData;
+
+// Gas added to the callee of a CALL or CALLCODE that transfers value
+const CALL_STIPEND: u64 = 2300;
+
+// One call frame of a transaction, like a frame of geth's callTracer
+pub struct Frame {
+    pub kind: &'static str,
+    pub from: H160,
+    pub to: H160,
+    pub value: U256,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub gas: u64,
+    pub gas_used: u64,
+    pub error: Option<String>,
+    pub reverted: bool,
+    pub calls: Vec<Frame>,
+    // Account whose storage and balance the frame's code works on
+    context_address: H160,
+    is_static: bool,
+    // Whether the gas forwarded by the caller was seen yet
+    funded: bool,
+    // Gas handed back to the caller when the frame finished
+    gas_left: Option<u64>,
+}
+
+// Builds the call tree from the executor and gasometer events forwarded by the tracer
+#[derive(Default)]
+pub struct CallRecorder {
+    stack: Vec<Frame>,
+    pub root: Option<Frame>,
+}
+
+impl CallRecorder {
+    pub fn enter_call(&mut self, code_address: H160, context: &evm::Context, input: &[u8], has_transfer: bool, is_static: bool) {
+        let parent = self.stack.last();
+        let kind = if is_static && !parent.is_some_and(|parent| parent.is_static) {
+            "STATICCALL"
+        } else if context.address != code_address {
+            if has_transfer { "CALLCODE" } else { "DELEGATECALL" }
+        } else {
+            "CALL"
+        };
+        // The caller is the account the calling code runs as, which for code run
+        // through a delegate call is the delegating contract
+        let from = parent.map_or(context.caller, |parent| parent.context_address);
+        self.stack.push(Frame::new(kind, from, code_address, context.address, context.apparent_value, input.to_vec(), is_static));
+    }
+
+    pub fn enter_create(&mut self, caller: H160, address: H160, scheme: CreateScheme, value: U256, init_code: &[u8]) {
+        let kind = match scheme {
+            CreateScheme::Create2 { .. } => "CREATE2",
+            _ => "CREATE",
+        };
+        self.stack.push(Frame::new(kind, caller, address, address, value, init_code.to_vec(), false));
+    }
+
+    // The first charge after entering a frame is the gas its caller forwards to it
+    pub fn record_cost(&mut self, cost: u64) {
+        if let Some(frame) = self.stack.last_mut() {
+            if !frame.funded {
+                let stipend = if (frame.kind == "CALL" || frame.kind == "CALLCODE") && !frame.value.is_zero() { CALL_STIPEND } else { 0 };
+                frame.gas = cost + stipend;
+                frame.funded = true;
+            }
+        }
+    }
+
+    // Unused gas is returned to the caller just before the frame exits
+    pub fn record_stipend(&mut self, stipend: u64) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.gas_left = Some(stipend);
+        }
+    }
+
+    pub fn exit(&mut self, reason: &ExitReason, return_value: &[u8]) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        frame.output = return_value.to_vec();
+        frame.gas_used = frame.gas.saturating_sub(frame.gas_left.unwrap_or(0));
+        match reason {
+            ExitReason::Succeed(_) => {},
+            ExitReason::Revert(_) => {
+                frame.reverted = true;
+                frame.error = Some("execution reverted".to_string());
+            },
+            ExitReason::Error(e) => frame.error = Some(format!("{:?}", e)),
+            ExitReason::Fatal(e) => frame.error = Some(format!("{:?}", e)),
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+impl Frame {
+    fn new(kind: &'static str, from: H160, to: H160, context_address: H160, value: U256, input: Vec<u8>, is_static: bool) -> Self {
+        Frame {
+            kind,
+            from,
+            to,
+            value,
+            input,
+            output: Vec::new(),
+            gas: 0,
+            gas_used: 0,
+            error: None,
+            reverted: false,
+            calls: Vec::new(),
+            context_address,
+            is_static,
+            funded: false,
+            gas_left: None,
+        }
+    }
+}
+
+pub fn print_call_tree(root: &Frame, contracts: &ContractsData) {
+    println!("Call tree:");
+    print_frame(root, contracts, 1);
+}
+
+fn print_frame(frame: &Frame, contracts: &ContractsData, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let value = if frame.value.is_zero() { String::new() } else { format!(" value: {}", frame.value) };
+    println!("{}{} {:?} -> {}{}  gas used: {}", indent, frame.kind, frame.from, describe_call(frame, contracts), value, frame.gas_used);
+    for call in &frame.calls {
+        print_frame(call, contracts, depth + 1);
+    }
+    println!("{}  {}", indent, describe_result(frame, contracts));
+}
+
+// "Contract.function(name: value, ...)" when the callee or selector is known
+pub fn describe_call(frame: &Frame, contracts: &ContractsData) -> String {
+    if frame.kind.starts_with("CREATE") {
+        return describe_create(frame, contracts);
+    }
+
+    let label = contract_label(frame.to, contracts);
+    if frame.input.len() < 4 {
+        return format!("{}.receive/fallback({})", label, hex_or_empty(&frame.input));
+    }
+    match find_function(frame, contracts) {
+        Some((contract_name, function)) => {
+            let args = function.decode_input(&frame.input[4..])
+                .map(|tokens| named_tokens(function.inputs.iter().map(|p| p.name.as_str()), &tokens))
+                .unwrap_or_else(|_| format!("undecodable 0x{}", hex::encode(&frame.input[4..])));
+            format!("{}({:?}).{}({})", contract_name, frame.to, function.name, args)
+        },
+        None => format!("{}.0x{}(0x{})", label, hex::encode(&frame.input[..4]), hex::encode(&frame.input[4..])),
+    }
+}
+
+pub fn describe_result(frame: &Frame, contracts: &ContractsData) -> String {
+    if frame.reverted {
+        return decode_revert(&frame.output, contracts);
+    }
+    if let Some(error) = &frame.error {
+        return format!("failed: {}", error);
+    }
+    if frame.kind.starts_with("CREATE") {
+        return format!("created {:?}", frame.to);
+    }
+    if frame.input.len() >= 4 {
+        if let Some((_, function)) = find_function(frame, contracts) {
+            if let Ok(tokens) = function.decode_output(&frame.output) {
+                return format!("returned [{}]", tokens.iter().map(format_token).collect::<Vec<_>>().join(", "));
+            }
+        }
+    }
+    format!("returned {}", hex_or_empty(&frame.output))
+}
+
+fn describe_create(frame: &Frame, contracts: &ContractsData) -> String {
+    let known = contracts.iter().find(|(_, contract_data)| {
+        !contract_data.bytecode.is_empty() && frame.input.starts_with(&contract_data.bytecode)
+    });
+    match known {
+        Some((contract_name, contract_data)) => {
+            let encoded_args = &frame.input[contract_data.bytecode.len()..];
+            let args = match contract_data.abi.constructor() {
+                Some(constructor) => {
+                    let types = constructor.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
+                    ethabi::decode(&types, encoded_args)
+                        .map(|tokens| named_tokens(constructor.inputs.iter().map(|p| p.name.as_str()), &tokens))
+                        .unwrap_or_else(|_| format!("undecodable 0x{}", hex::encode(encoded_args)))
+                },
+                None => String::new(),
+            };
+            format!("new {}({}) at {:?}", contract_name, args, frame.to)
+        },
+        None => format!("new contract at {:?} ({} bytes of init code)", frame.to, frame.input.len()),
+    }
+}
+
+// Looks the selector up in the ABI of the called contract first, then in every ABI
+fn find_function<'a>(frame: &Frame, contracts: &'a ContractsData) -> Option<(&'a str, &'a ethabi::Function)> {
+    let selector = &frame.input[..4];
+    let by_address = contracts.iter().filter(|(_, contract_data)| contract_data.address == Some(frame.to));
+    let by_selector = contracts.iter();
+    by_address.chain(by_selector).find_map(|(contract_name, contract_data)| {
+        contract_data.abi.functions()
+            .find(|function| function.short_signature() == selector)
+            .map(|function| (contract_name.as_str(), function))
+    })
+}
+
+fn contract_label(address: H160, contracts: &ContractsData) -> String {
+    contracts.iter()
+        .find(|(_, contract_data)| contract_data.address == Some(address))
+        .map(|(contract_name, _)| format!("{}({:?})", contract_name, address))
+        .unwrap_or_else(|| format!("{:?}", address))
+}
+
+fn named_tokens<'a>(names: impl Iterator<Item = &'a str>, tokens: &[Token]) -> String {
+    names.zip(tokens.iter())
+        .map(|(name, token)| if name.is_empty() { format_token(token) } else { format!("{}: {}", name, format_token(token)) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn hex_or_empty(data: &[u8]) -> String {
+    if data.is_empty() { "nothing".to_string() } else { format!("0x{}", hex::encode(data)) }
+}
+
+// The tree in the format of geth's callTracer, with the decoded call and result added
+pub fn call_tree_json(frame: &Frame, contracts: &ContractsData) -> Value {
+    let mut object = Map::new();
+    object.insert("type".into(), json!(frame.kind));
+    object.insert("from".into(), json!(format!("{:?}", frame.from)));
+    object.insert("to".into(), json!(format!("{:?}", frame.to)));
+    object.insert("value".into(), json!(format!("{:#x}", frame.value)));
+    object.insert("gas".into(), json!(format!("{:#x}", frame.gas)));
+    object.insert("gasUsed".into(), json!(format!("{:#x}", frame.gas_used)));
+    object.insert("input".into(), json!(format!("0x{}", hex::encode(&frame.input))));
+    object.insert("output".into(), json!(format!("0x{}", hex::encode(&frame.output))));
+    if let Some(error) = &frame.error {
+        object.insert("error".into(), json!(error));
+    }
+    object.insert("decodedCall".into(), json!(describe_call(frame, contracts)));
+    object.insert("decodedResult".into(), json!(describe_result(frame, contracts)));
+    if !frame.calls.is_empty() {
+        object.insert("calls".into(), Value::Array(frame.calls.iter().map(|call| call_tree_json(call, contracts)).collect()));
+    }
+    Value::Object(object)
+}
+
+pub fn write_call_tree(path: &str, frame: &Frame, contracts: &ContractsData) -> Result<(), io::Error> {
+    let contents = serde_json::to_string_pretty(&call_tree_json(frame, contracts)).map_err(io::Error::other)?;
+    fs::write(path, contents)
+}
@@ -0,0 +1,641 @@
+use ethereum_types::{H160, H256, U256};
+use evm::backend::{ApplyBackend, Log, MemoryAccount, MemoryBackend, MemoryVicinity};
+use evm::executor::stack::{Accessed, MemoryStackState, StackExecutor, StackState, StackSubstateMetadata};
+use evm::gasometer::{self, Gasometer};
+use evm::{ExitError, ExitReason};
+
+use rlp::RlpStream;
+use sha3::{Digest, Keccak256};
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+
+use crate::access_list::AccessList;
+use crate::block::{self, Block};
+use crate::gas_report::GasReport;
+use crate::history::{self, Receipt};
+use crate::precompiles::Precompiles;
+use crate::tracer::{self, Trace, Tracing};
+
+type Executor<'config, 'backend, 'vicinity> =
+    StackExecutor<'config, 'config, MemoryStackState<'backend, 'config, MemoryBackend<'vicinity>>, Precompiles>;
+
+// The world state together with the rules transactions are executed under
+pub struct Chain {
+    // The pending block transactions run in, as seen by contracts. Gas price and origin
+    // are set per transaction.
+    pub vicinity: MemoryVicinity,
+    // Sealed blocks, oldest first
+    pub blocks: Vec<Block>,
+    // Seconds between a block and the next
+    pub block_time: u64,
+    // Seal a block after every transaction, otherwise only when mined
+    pub automine: bool,
+    // Gas used and transactions run in the pending block so far
+    pub pending_gas_used: u64,
+    pub pending_transactions: Vec<H256>,
+    // Receipts of every transaction run, oldest first
+    pub receipts: Vec<Receipt>,
+    pub state: BTreeMap<H160, MemoryAccount>,
+    pub config: evm::Config,
+    pub precompiles: Precompiles,
+    // Gas limit and price of transactions that don't set their own
+    pub gas_limit: u64,
+    pub gas_price: U256,
+    // Access list of the next transaction sent from the session or a scenario step,
+    // which takes it
+    pub access_list: AccessList,
+    // Opcode steps
+    pub tracing: Tracing,
+    // Call tree of nested calls and creates
+    pub call_tracing: Tracing,
+    // Trace of the last transaction, when either kind of tracing is on
+    pub last_trace: Option<Trace>,
+    // Gas of every successful deployment and call, by contract and function
+    pub gas_report: GasReport,
+}
+
+impl Chain {
+    // The vicinity is sealed as the first block, transactions go into the one after it
+    pub fn new(
+        vicinity: MemoryVicinity,
+        config: evm::Config,
+        precompiles: Precompiles,
+        genesis: BTreeMap<H160, MemoryAccount>,
+        gas_limit: u64,
+        gas_price: U256,
+    ) -> Self {
+        let mut chain = Chain {
+            vicinity,
+            blocks: Vec::new(),
+            block_time: 12,
+            automine: true,
+            pending_gas_used: 0,
+            pending_transactions: Vec::new(),
+            receipts: Vec::new(),
+            state: genesis,
+            config,
+            precompiles,
+            gas_limit,
+            gas_price,
+            access_list: Vec::new(),
+            tracing: Tracing::Off,
+            call_tracing: Tracing::Off,
+            last_trace: None,
+            gas_report: GasReport::default(),
+        };
+        chain.seal_block();
+        chain
+    }
+
+    pub fn nonce(&self, address: H160) -> U256 {
+        self.state.get(&address).map(|account| account.nonce).unwrap_or_default()
+    }
+
+    pub fn balance(&self, address: H160) -> U256 {
+        self.state.get(&address).map(|account| account.balance).unwrap_or_default()
+    }
+
+    pub fn latest_block(&self) -> &Block {
+        self.blocks.last().expect("the first block is sealed on creation")
+    }
+
+    // Seals the pending block and opens the next one on top of it
+    fn seal_block(&mut self) {
+        let parent_hash = self.blocks.last().map(|parent| parent.hash).unwrap_or_default();
+        let transactions = mem::take(&mut self.pending_transactions);
+        let sealed = Block::seal(parent_hash, &self.vicinity, self.pending_gas_used, transactions);
+        // The receipts of the block's transactions are the latest ones
+        for receipt in self.receipts.iter_mut().rev().take(sealed.transactions.len()) {
+            receipt.block_hash = Some(sealed.hash);
+        }
+
+        // BLOCKHASH sees the last 256 blocks, most recent first
+        self.vicinity.block_hashes.insert(0, sealed.hash);
+        self.vicinity.block_hashes.truncate(256);
+        self.vicinity.block_number += U256::one();
+        self.vicinity.block_timestamp += U256::from(self.block_time);
+        if self.config.has_base_fee {
+            self.vicinity.block_base_fee_per_gas =
+                block::next_base_fee(sealed.base_fee, sealed.gas_used, sealed.gas_limit);
+        }
+        // After the merge every block gets its own randomness, taken from its parent
+        if self.vicinity.block_randomness.is_some() {
+            self.vicinity.block_randomness = Some(H256::from_slice(&Keccak256::digest(sealed.hash)));
+        }
+        self.pending_gas_used = 0;
+        self.blocks.push(sealed);
+    }
+
+    pub fn mine(&mut self, count: u64) {
+        for _ in 0..count {
+            self.seal_block();
+        }
+    }
+
+    // Sets the timestamp of the pending block, which has to be after its parent's
+    pub fn warp(&mut self, timestamp: U256) -> Result<(), io::Error> {
+        let parent_timestamp = self.latest_block().timestamp;
+        if timestamp <= parent_timestamp {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("timestamp {} is not after the latest block's {}", timestamp, parent_timestamp)));
+        }
+        self.vicinity.block_timestamp = timestamp;
+        Ok(())
+    }
+
+    // Runs a single transaction on a fresh executor and commits the resulting state
+    // changes. The sender pays gas limit * gas price up front and gets back what is
+    // left unused, the fee above the base fee goes to the coinbase. Transactions that
+    // can't be paid for or don't fit in the pending block are rejected without running.
+    // With automine on the block is sealed right after.
+    pub fn transact(&mut self, transaction: Transaction) -> Result<Outcome, io::Error> {
+        let gas_limit = transaction.gas_limit.unwrap_or(self.gas_limit);
+        let gas_price = transaction.gas_price.unwrap_or(self.gas_price);
+
+        if U256::from(gas_limit) > self.vicinity.block_gas_limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("gas limit {} exceeds the block gas limit {}", gas_limit, self.vicinity.block_gas_limit)));
+        }
+        // Below the intrinsic gas the transaction can't even start, so it is not included
+        let intrinsic_gas = intrinsic_gas(&self.config, &transaction);
+        if gas_limit < intrinsic_gas {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("intrinsic gas too low: gas limit {} is below {}", gas_limit, intrinsic_gas)));
+        }
+        self.check_access_list(&transaction)?;
+        let gas_left = self.vicinity.block_gas_limit.saturating_sub(U256::from(self.pending_gas_used));
+        if U256::from(gas_limit) > gas_left {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("gas limit {} exceeds the {} gas left in the pending block, mine it first", gas_limit, gas_left)));
+        }
+        let nonce = self.nonce(transaction.caller);
+        match transaction.nonce {
+            Some(expected) if expected < nonce => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("nonce too low: {:?} is at nonce {}, got {}", transaction.caller, nonce, expected))),
+            Some(expected) if expected > nonce => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("nonce too high: {:?} is at nonce {}, got {}", transaction.caller, nonce, expected))),
+            _ => {},
+        }
+        if gas_price < self.vicinity.block_base_fee_per_gas {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("gas price {} is below the base fee {}", gas_price, self.vicinity.block_base_fee_per_gas)));
+        }
+        let upfront_fee = U256::from(gas_limit).checked_mul(gas_price)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "gas limit * gas price overflows"))?;
+        let balance = self.balance(transaction.caller);
+        if upfront_fee.checked_add(transaction.value).is_none_or(|cost| balance < cost) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("insufficient funds for gas * price + value: {:?} has {} wei, needs {} + {}",
+                    transaction.caller, balance, upfront_fee, transaction.value)));
+        }
+        self.state.entry(transaction.caller).or_insert_with(empty_account).balance = balance - upfront_fee;
+
+        let caller = transaction.caller;
+        let transaction_hash = transaction.hash
+            .unwrap_or_else(|| transaction_hash(&transaction, nonce, gas_limit, gas_price, self.vicinity.chain_id));
+        let (to, value, input) = (transaction.to, transaction.value, transaction.data.clone());
+        let mut outcome = self.execute(transaction, gas_limit, gas_price, Mode::Commit);
+        outcome.transaction_hash = Some(transaction_hash);
+
+        // Give back the unused gas and pay the coinbase its tip, the base fee is burnt
+        let unused_fee = U256::from(gas_limit - outcome.gas_used) * gas_price;
+        self.state.entry(caller).or_insert_with(empty_account).balance += unused_fee;
+        let tip = U256::from(outcome.gas_used) * (gas_price - self.vicinity.block_base_fee_per_gas);
+        if !tip.is_zero() {
+            self.state.entry(self.vicinity.block_coinbase).or_insert_with(empty_account).balance += tip;
+        }
+
+        self.pending_gas_used += outcome.gas_used;
+        self.receipts.push(Receipt {
+            transaction_hash,
+            transaction_index: self.pending_transactions.len(),
+            block_number: self.vicinity.block_number,
+            block_hash: None,
+            from: caller,
+            to,
+            nonce,
+            value,
+            input,
+            gas_limit,
+            contract_address: (to.is_none() && outcome.exit_reason.is_succeed())
+                .then(|| crate::compute_contract_address(caller, nonce)),
+            exit_reason: outcome.exit_reason.clone(),
+            output: outcome.output.clone(),
+            gas_used: outcome.gas_used,
+            cumulative_gas_used: self.pending_gas_used,
+            effective_gas_price: gas_price,
+            logs: outcome.logs.clone(),
+            logs_bloom: history::logs_bloom(&outcome.logs),
+            signed: None,
+        });
+        self.pending_transactions.push(transaction_hash);
+        if self.automine {
+            self.seal_block();
+        }
+        Ok(outcome)
+    }
+
+    // Runs a transaction as a static call on a copy of the state that is thrown away
+    // afterwards, for view and pure functions. Nothing is charged and the sender's
+    // nonce stays the same.
+    pub fn call(&mut self, transaction: Transaction) -> Result<Outcome, io::Error> {
+        self.dry_run(transaction, Mode::Static)
+    }
+
+    // Like `call` but without making the call static, so any transaction can be tried
+    // out the way eth_call and eth_estimateGas do
+    pub fn simulate(&mut self, transaction: Transaction) -> Result<Outcome, io::Error> {
+        self.dry_run(transaction, Mode::Simulate)
+    }
+
+    // Before Berlin there is nothing for an access list to warm up
+    fn check_access_list(&self, transaction: &Transaction) -> Result<(), io::Error> {
+        if !transaction.access_list.is_empty() && !self.config.increase_state_access_gas {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "access lists need the Berlin rules or later"));
+        }
+        Ok(())
+    }
+
+    // Works out the access list of a transaction like eth_createAccessList: it runs
+    // without committing, the addresses and storage slots it touched become its access
+    // list, and it runs again with that list until the list stops changing. Addresses
+    // that are warm anyway (the sender, the recipient, precompiles and from Shanghai the
+    // coinbase) are left out with their slots, so the list never adds gas.
+    pub fn create_access_list(&mut self, transaction: Transaction) -> Result<(AccessList, Outcome), io::Error> {
+        if !self.config.increase_state_access_gas {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "access lists need the Berlin rules or later"));
+        }
+        let recipient = transaction.to
+            .unwrap_or_else(|| crate::compute_contract_address(transaction.caller, self.nonce(transaction.caller)));
+        let mut warm = vec![transaction.caller, recipient];
+        if self.config.warm_coinbase_address {
+            warm.push(self.vicinity.block_coinbase);
+        }
+
+        let mut access_list = transaction.access_list.clone();
+        // The list changes the gas left, so code that branches on gas may touch
+        // something else on the next run. A few runs are enough to settle that.
+        let mut runs = 0;
+        loop {
+            runs += 1;
+            let outcome = self.simulate(Transaction { access_list: access_list.clone(), ..transaction.clone() })?;
+            let accessed = outcome.accessed.clone().unwrap_or_default();
+            // Listing a warm address costs 2400 gas for nothing, its slots only make up
+            // for that from 25 on
+            let listed = |address: &H160| !warm.contains(address) && !self.precompiles.contains(*address);
+            let mut touched: BTreeMap<H160, Vec<H256>> = BTreeMap::new();
+            for address in accessed.accessed_addresses.into_iter().filter(listed) {
+                touched.entry(address).or_default();
+            }
+            for (address, key) in accessed.accessed_storage.into_iter().filter(|(address, _)| listed(address)) {
+                touched.entry(address).or_default().push(key);
+            }
+            let touched: Vec<_> = touched.into_iter().collect();
+            if touched == access_list || runs == 10 {
+                return self.prune_access_list(transaction, access_list, outcome);
+            }
+            access_list = touched;
+        }
+    }
+
+    // Drops the entries that cost more than they save, like the address of a contract
+    // the transaction creates, which is warm from its creation on
+    fn prune_access_list(
+        &mut self,
+        transaction: Transaction,
+        mut access_list: AccessList,
+        mut outcome: Outcome,
+    ) -> Result<(AccessList, Outcome), io::Error> {
+        let mut index = 0;
+        while index < access_list.len() {
+            let mut shorter = access_list.clone();
+            shorter.remove(index);
+            let trial = self.simulate(Transaction { access_list: shorter.clone(), ..transaction.clone() })?;
+            if trial.gas_used < outcome.gas_used && trial.exit_reason.is_succeed() == outcome.exit_reason.is_succeed() {
+                (access_list, outcome) = (shorter, trial);
+            } else {
+                index += 1;
+            }
+        }
+        Ok((access_list, outcome))
+    }
+
+    fn dry_run(&mut self, transaction: Transaction, mode: Mode) -> Result<Outcome, io::Error> {
+        let gas_limit = transaction.gas_limit.unwrap_or(self.gas_limit);
+        let gas_price = transaction.gas_price.unwrap_or(self.gas_price);
+        if U256::from(gas_limit) > self.vicinity.block_gas_limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("gas limit {} exceeds the block gas limit {}", gas_limit, self.vicinity.block_gas_limit)));
+        }
+        self.check_access_list(&transaction)?;
+        Ok(self.execute(transaction, gas_limit, gas_price, mode))
+    }
+
+    // Runs the transaction on a fresh executor and handles its state changes by the mode
+    fn execute(&mut self, transaction: Transaction, gas_limit: u64, gas_price: U256, mode: Mode) -> Outcome {
+        self.vicinity.gas_price = gas_price;
+        self.vicinity.origin = transaction.caller;
+        let intrinsic_gas = intrinsic_gas(&self.config, &transaction);
+
+        let state = if mode == Mode::Commit { mem::take(&mut self.state) } else { self.state.clone() };
+        let mut backend = MemoryBackend::new(&self.vicinity, state);
+        let mut metadata = StackSubstateMetadata::new(gas_limit, &self.config);
+        if mode == Mode::Static {
+            // Frames below a static one are static too
+            metadata = metadata.spit_child(gas_limit, true);
+        }
+        let stack_state = MemoryStackState::new(metadata, &mut backend);
+        let mut executor = StackExecutor::new_with_precompiles(stack_state, &self.config, &self.precompiles);
+
+        let run = |executor: &mut Executor<'_, '_, '_>| match transaction.to {
+            Some(address) => executor.transact_call(
+                transaction.caller,
+                address,
+                transaction.value,
+                transaction.data,
+                gas_limit,
+                transaction.access_list,
+            ),
+            None => executor.transact_create(
+                transaction.caller,
+                transaction.value,
+                transaction.data,
+                gas_limit,
+                transaction.access_list,
+            ),
+        };
+
+        let (exit_reason, output) = if self.tracing == Tracing::Off && self.call_tracing == Tracing::Off {
+            run(&mut executor)
+        } else {
+            let ((exit_reason, output), steps, mut call_tree) =
+                tracer::record(self.tracing != Tracing::Off, || run(&mut executor));
+            // The transaction frame accounts for all gas, like in geth
+            if let Some(root) = call_tree.as_mut() {
+                root.gas = gas_limit;
+                root.gas_used = executor.used_gas();
+            }
+            self.last_trace = Some(Trace {
+                steps,
+                call_tree,
+                gas_used: executor.used_gas(),
+                failed: !exit_reason.is_succeed(),
+                return_value: output.clone(),
+            });
+            (exit_reason, output)
+        };
+
+        // used_gas() already has the refund taken off
+        let gas_used = executor.used_gas();
+        let gas_refund = executor.state().metadata().gasometer().total_used_gas() - gas_used;
+        let accessed = executor.state().metadata().accessed().clone();
+
+        let (values, logs) = executor.into_state().deconstruct();
+        let logs: Vec<Log> = logs.into_iter().collect();
+        if mode == Mode::Commit {
+            backend.apply(values, logs.clone(), false);
+            self.state = mem::take(backend.state_mut());
+        }
+
+        Outcome {
+            transaction_hash: None,
+            block_number: self.vicinity.block_number,
+            exit_reason,
+            output,
+            logs,
+            gas_limit,
+            gas_used,
+            intrinsic_gas,
+            gas_refund,
+            accessed,
+            // Dry runs aren't charged
+            fee: if mode == Mode::Commit { U256::from(gas_used) * gas_price } else { U256::zero() },
+        }
+    }
+}
+
+// What happens to the state changes of a transaction
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Commit,
+    // Run on a copy of the state that is dropped afterwards
+    Simulate,
+    // Like Simulate, with every frame static
+    Static,
+}
+
+fn empty_account() -> MemoryAccount {
+    MemoryAccount {
+        nonce: U256::zero(),
+        balance: U256::zero(),
+        storage: BTreeMap::new(),
+        code: Vec::new(),
+    }
+}
+
+#[derive(Clone)]
+pub struct Transaction {
+    pub caller: H160,
+    // None deploys `data` as init code
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    // The chain's defaults are used when not set
+    pub gas_limit: Option<u64>,
+    pub gas_price: Option<U256>,
+    pub access_list: AccessList,
+    // Checked against the sender's nonce when set
+    pub nonce: Option<U256>,
+    // Signed transactions come with their hash, others get one made up from their fields
+    pub hash: Option<H256>,
+}
+
+pub struct Outcome {
+    // Only transactions that were committed have a hash
+    pub transaction_hash: Option<H256>,
+    // Block the transaction ran in
+    pub block_number: U256,
+    pub exit_reason: ExitReason,
+    pub output: Vec<u8>,
+    pub logs: Vec<Log>,
+    pub gas_limit: u64,
+    // Gas charged to the sender, after the refund
+    pub gas_used: u64,
+    // Base cost of the transaction and its calldata, charged before any code runs
+    pub intrinsic_gas: u64,
+    pub gas_refund: u64,
+    // Addresses and storage slots that were warm at the end, from Berlin on. Those
+    // only touched by reverted calls are cold again.
+    pub accessed: Option<Accessed>,
+    // Wei paid for the gas used
+    pub fee: U256,
+}
+
+impl Outcome {
+    pub fn print_inclusion(&self) {
+        if let Some(hash) = self.transaction_hash {
+            println!("Transaction {:?} in block {}", hash, self.block_number);
+        }
+    }
+
+    pub fn print_gas(&self) {
+        println!("Gas used: {} of {} (intrinsic {}, execution {}, refund {}), fee {} wei",
+            self.gas_used,
+            self.gas_limit,
+            self.intrinsic_gas,
+            (self.gas_used + self.gas_refund).saturating_sub(self.intrinsic_gas),
+            self.gas_refund,
+            self.fee);
+    }
+
+    // Explains failures other than reverts, which are decoded against the ABIs instead
+    pub fn describe_failure(&self) -> String {
+        match &self.exit_reason {
+            ExitReason::Error(ExitError::OutOfGas) if self.intrinsic_gas > self.gas_limit => {
+                format!("ran out of gas: the gas limit {} is below the intrinsic cost {}", self.gas_limit, self.intrinsic_gas)
+            },
+            ExitReason::Error(ExitError::OutOfGas) => {
+                format!("ran out of gas: all {} gas of the limit was used", self.gas_limit)
+            },
+            other => format!("failed: {:?}", other),
+        }
+    }
+}
+
+// Transactions sent without a signature are identified by a hash of their fields and
+// sender, which is unique as long as the sender's nonce is part of it
+fn transaction_hash(transaction: &Transaction, nonce: U256, gas_limit: u64, gas_price: U256, chain_id: U256) -> H256 {
+    let mut stream = RlpStream::new_list(8);
+    stream.append(&nonce);
+    stream.append(&gas_price);
+    stream.append(&gas_limit);
+    match transaction.to {
+        Some(to) => stream.append(&to),
+        None => stream.append_empty_data(),
+    };
+    stream.append(&transaction.value);
+    stream.append(&transaction.data);
+    stream.append(&chain_id);
+    stream.append(&transaction.caller);
+    H256::from_slice(&Keccak256::digest(stream.out()))
+}
+
+// Measured on a scratch gasometer so it follows the same rules as the executor
+pub fn intrinsic_gas(config: &evm::Config, transaction: &Transaction) -> u64 {
+    let cost = match transaction.to {
+        Some(_) => gasometer::call_transaction_cost(&transaction.data, &transaction.access_list),
+        None => gasometer::create_transaction_cost(&transaction.data, &transaction.access_list),
+    };
+    let mut gasometer = Gasometer::new(u64::MAX, config);
+    match gasometer.record_transaction(cost) {
+        Ok(()) => gasometer.total_used_gas(),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::hardfork::Hardfork;
+
+    // A Shanghai chain with id 31337, no base fee and a single funded account
+    pub fn chain_with(sender: H160, balance: U256) -> Chain {
+        let vicinity = MemoryVicinity {
+            gas_price: U256::zero(),
+            origin: H160::zero(),
+            chain_id: U256::from(31337),
+            block_hashes: Vec::new(),
+            block_number: U256::zero(),
+            block_coinbase: H160::zero(),
+            block_timestamp: U256::zero(),
+            block_difficulty: U256::zero(),
+            block_randomness: Some(H256::zero()),
+            block_gas_limit: U256::from(30_000_000),
+            block_base_fee_per_gas: U256::zero(),
+        };
+        let mut genesis = BTreeMap::new();
+        genesis.insert(sender, MemoryAccount { nonce: U256::zero(), balance, storage: BTreeMap::new(), code: Vec::new() });
+        Chain::new(vicinity, Hardfork::Shanghai.config(), Precompiles::new(Hardfork::Shanghai), genesis, 1_000_000, U256::one())
+    }
+
+    fn transfer(caller: H160, gas_limit: u64) -> Transaction {
+        Transaction {
+            caller,
+            to: Some(H160::repeat_byte(0x22)),
+            value: U256::from(5),
+            data: Vec::new(),
+            gas_limit: Some(gas_limit),
+            gas_price: None,
+            access_list: Vec::new(),
+            nonce: None,
+            hash: None,
+        }
+    }
+
+    fn contract(chain: &mut Chain, address: H160, code: &str) {
+        let account = MemoryAccount { nonce: U256::one(), balance: U256::zero(), storage: BTreeMap::new(), code: hex::decode(code).unwrap() };
+        chain.state.insert(address, account);
+    }
+
+    // The gas of the transaction with and without the list it gets from create_access_list
+    fn gas_with_and_without_list(chain: &mut Chain, transaction: Transaction) -> (AccessList, u64, u64) {
+        let (access_list, _) = chain.create_access_list(transaction.clone()).unwrap();
+        let with = chain.simulate(Transaction { access_list: access_list.clone(), ..transaction.clone() }).unwrap();
+        let without = chain.simulate(transaction).unwrap();
+        assert!(with.exit_reason.is_succeed() && without.exit_reason.is_succeed());
+        (access_list, with.gas_used, without.gas_used)
+    }
+
+    #[test]
+    fn created_access_lists_leave_warm_addresses_out() {
+        let sender = H160::repeat_byte(0x11);
+        let (recipient, other) = (H160::repeat_byte(0xaa), H160::repeat_byte(0xbb));
+        let mut chain = chain_with(sender, U256::exp10(18));
+        // Reads its own slot 0, then static calls the other contract, which reads its slot 1
+        contract(&mut chain, recipient, &format!("60005450600060006000600073{}5afa5000", "bb".repeat(20)));
+        contract(&mut chain, other, "6001545000");
+
+        let transaction = Transaction { to: Some(recipient), value: U256::zero(), ..transfer(sender, 100_000) };
+        let (access_list, with, without) = gas_with_and_without_list(&mut chain, transaction);
+        assert_eq!(access_list, vec![(other, vec![H256::from_low_u64_be(1)])]);
+        // 2400 + 1900 up front saves 2500 + 2000 on the cold accesses
+        assert_eq!(with + 200, without);
+    }
+
+    #[test]
+    fn created_access_lists_never_cost_more() {
+        let sender = H160::repeat_byte(0x11);
+        let recipient = H160::repeat_byte(0xaa);
+        let mut chain = chain_with(sender, U256::exp10(18));
+        // Creates an empty contract, whose address is warm from its creation on
+        contract(&mut chain, recipient, "600060006000f05000");
+
+        let transaction = Transaction { to: Some(recipient), value: U256::zero(), ..transfer(sender, 100_000) };
+        let (access_list, with, without) = gas_with_and_without_list(&mut chain, transaction);
+        assert!(access_list.is_empty());
+        assert!(with <= without);
+    }
+
+    #[test]
+    fn rejects_transactions_below_the_intrinsic_gas() {
+        let sender = H160::repeat_byte(0x11);
+        let balance = U256::from(1_000_000);
+        let mut chain = chain_with(sender, balance);
+
+        let Err(error) = chain.transact(transfer(sender, 100)) else {
+            panic!("a transaction below the intrinsic gas was included");
+        };
+        assert_eq!(error.to_string(), "intrinsic gas too low: gas limit 100 is below 21000");
+        // Nothing was charged or included
+        assert_eq!(chain.balance(sender), balance);
+        assert_eq!(chain.nonce(sender), U256::zero());
+        assert!(chain.receipts.is_empty());
+
+        let outcome = chain.transact(transfer(sender, 21_000)).unwrap();
+        assert!(matches!(outcome.exit_reason, ExitReason::Succeed(_)));
+        assert_eq!(chain.nonce(sender), U256::one());
+        assert_eq!(chain.balance(sender), balance - U256::from(21_000 + 5));
+    }
+}
@@ -0,0 +1,174 @@
+use clap::{Args, Parser, Subcommand};
+
+use crate::hardfork::Hardfork;
+
+#[derive(Parser)]
+#[command(name = "lithevm", version, about = "A playground for taking Rust EVM for a spin")]
+pub struct Cli {
+    #[command(flatten)]
+    pub options: Options,
+
+    // Without a subcommand the interactive session is started
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Args)]
+pub struct Options {
+    /// Directory containing the .sol files
+    #[arg(long, global = true, default_value = "./contracts")]
+    pub contracts_dir: String,
+
+    /// Directory where the ABI and bytecode of each contract are written
+    #[arg(long, global = true, default_value = "./build/contracts")]
+    pub build_dir: String,
+
+    /// Path to the solc binary
+    #[arg(long, global = true, default_value = "solc")]
+    pub solc: String,
+
+    /// Chain id exposed to contracts through the CHAINID opcode
+    #[arg(long, global = true, default_value_t = 1)]
+    pub chain_id: u64,
+
+    /// EVM rules transactions are executed under
+    #[arg(long, global = true, value_enum, default_value_t = Hardfork::Shanghai)]
+    pub hardfork: Hardfork,
+
+    /// JSON file overriding fields of the hardfork's EVM config, e.g. {"gas_sload_cold": 0}
+    #[arg(long, global = true)]
+    pub evm_config: Option<String>,
+
+    /// Gas limit of every deployment and call
+    #[arg(long, global = true, default_value_t = 30_000_000)]
+    pub gas_limit: u64,
+
+    /// Gas price of every deployment and call, in wei or with a unit like "2 gwei".
+    /// The sender pays gas limit * price up front and gets the unused part back.
+    #[arg(long, global = true, default_value = "0")]
+    pub gas_price: String,
+
+    /// Most gas the transactions of a block may use together, larger gas limits are rejected
+    #[arg(long, global = true, default_value_t = 30_000_000)]
+    pub block_gas_limit: u64,
+
+    /// Base fee of the first block, in wei or with a unit. From London on it follows EIP-1559
+    /// from one block to the next, a zero base fee stays zero.
+    #[arg(long, global = true, default_value = "0")]
+    pub base_fee: String,
+
+    /// Timestamp of the first block in seconds, the current time if not given
+    #[arg(long, global = true)]
+    pub timestamp: Option<u64>,
+
+    /// Seconds between a block and the next
+    #[arg(long, global = true, default_value_t = 12)]
+    pub block_time: u64,
+
+    /// Hex private key of the deployer, the first account is used if not given
+    #[arg(long, global = true)]
+    pub deployer_key: Option<String>,
+
+    /// BIP-39 mnemonic the funded accounts are derived from, anvil's by default
+    #[arg(long, global = true, default_value = crate::keys::DEFAULT_MNEMONIC)]
+    pub mnemonic: String,
+
+    /// Number of funded accounts derived from the mnemonic
+    #[arg(long, global = true, default_value_t = 10)]
+    pub accounts: u32,
+
+    /// Starting balance of every account, in wei or with a unit like "100 ether"
+    #[arg(long, global = true, default_value = "10000 ether")]
+    pub balance: String,
+
+    /// File the world state is loaded from at startup, if it exists, and saved to on exit
+    #[arg(long, global = true)]
+    pub state: Option<String>,
+
+    /// geth genesis, geth dump or anvil dump-state JSON file to load accounts from before deploying
+    #[arg(long, global = true)]
+    pub fork_state: Option<String>,
+
+    /// Print the opcode trace of every transaction
+    #[arg(long, global = true)]
+    pub trace: bool,
+
+    /// Write the opcode trace of the last transaction to this file as geth structLogs JSON
+    #[arg(long, global = true)]
+    pub trace_json: Option<String>,
+
+    /// Print the tree of nested calls and creates of every transaction
+    #[arg(long, global = true)]
+    pub call_trace: bool,
+
+    /// Write the call tree of the last transaction to this file as geth callTracer JSON
+    #[arg(long, global = true)]
+    pub call_trace_json: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Compile the contracts and exit
+    Compile,
+    /// Deploy a single contract with the given constructor arguments
+    Deploy {
+        /// Contract name, the .sol file name without extension
+        contract: String,
+        /// Constructor arguments, using the same syntax as the prompts
+        #[arg(allow_negative_numbers = true)]
+        args: Vec<String>,
+        /// Wei sent with the deployment, units like "1 ether" are accepted
+        #[arg(long)]
+        value: Option<String>,
+        /// Sender address or account number, the deployer by default
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Deploy a contract and call one of its functions
+    Call {
+        /// Contract name, the .sol file name without extension
+        contract: String,
+        /// Function to call
+        function: String,
+        /// Function arguments, using the same syntax as the prompts
+        #[arg(allow_negative_numbers = true)]
+        args: Vec<String>,
+        /// Constructor argument used for the deployment, repeat for each parameter
+        #[arg(long = "constructor-arg", allow_negative_numbers = true)]
+        constructor_args: Vec<String>,
+        /// Wei sent with the call, units like "1 ether" are accepted
+        #[arg(long)]
+        value: Option<String>,
+        /// Sender address or account number, the deployer by default
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Send ether and optional calldata to an address, reaching receive() or fallback() of contracts
+    Send {
+        /// Recipient address, account number or deployed contract name
+        to: String,
+        /// Wei to send, units like "1 ether" are accepted
+        value: String,
+        /// Hex calldata
+        #[arg(long)]
+        data: Option<String>,
+        /// Sender address or account number, the deployer by default
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Deploy every contract and start the interactive session (the default)
+    Repl,
+    /// Run a scenario file headlessly, exits non-zero if any step fails
+    Run {
+        /// Path to the JSON scenario file
+        scenario: String,
+    },
+    /// Serve the chain over HTTP JSON-RPC, for wallets, scripts and tools like cast
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 8545)]
+        port: u16,
+    },
+}
@@ -0,0 +1,55 @@
+use ethabi::{Event, RawLog};
+use evm::backend::Log;
+
+use crate::abi_format::format_token;
+use crate::ContractsData;
+
+// Finds the ABI event a log was emitted for. The emitting contract's own ABI is
+// tried first, then every other known contract, since libraries and inherited
+// contracts can emit events declared elsewhere.
+fn find_event<'a>(log: &Log, contracts: &'a ContractsData) -> Option<(&'a str, &'a Event)> {
+    let topic0 = log.topics.first()?;
+
+    let emitter = contracts.iter()
+        .filter(|(_, contract_data)| contract_data.address == Some(log.address));
+    let others = contracts.iter()
+        .filter(|(_, contract_data)| contract_data.address != Some(log.address));
+
+    emitter.chain(others).find_map(|(contract_name, contract_data)| {
+        contract_data.abi.events()
+            .find(|event| !event.anonymous && event.signature() == *topic0)
+            .map(|event| (contract_name.as_str(), event))
+    })
+}
+
+pub fn print_logs(logs: &[Log], contracts: &ContractsData) {
+    if logs.is_empty() {
+        return;
+    }
+
+    println!("Events:");
+    for (i, log) in logs.iter().enumerate() {
+        let raw_log = RawLog { topics: log.topics.clone(), data: log.data.clone() };
+        let decoded = find_event(log, contracts).and_then(|(contract_name, event)| {
+            event.parse_log(raw_log).ok().map(|parsed| (contract_name, event, parsed))
+        });
+
+        match decoded {
+            Some((contract_name, event, parsed)) => {
+                println!("  [{}] {}.{} emitted by {:?}", i, contract_name, event.name, log.address);
+                // parse_log returns params in declaration order, same as event.inputs
+                for (input, param) in event.inputs.iter().zip(parsed.params.iter()) {
+                    let indexed_marker = if input.indexed { " (indexed)" } else { "" };
+                    println!("      {} [{}]{}: {}", param.name, input.kind, indexed_marker, format_token(&param.value));
+                }
+            },
+            None => {
+                println!("  [{}] Unknown event emitted by {:?}", i, log.address);
+                for (j, topic) in log.topics.iter().enumerate() {
+                    println!("      topic{}: {:?}", j, topic);
+                }
+                println!("      data: 0x{}", hex::encode(&log.data));
+            },
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use ethereum_types::{H160, H256, U256};
+use evm::backend::MemoryAccount;
+use serde_json::{Map, Value};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+// Reads the accounts of a state dump taken from a real chain. Accepted layouts:
+//
+// - a geth genesis file, accounts under "alloc"
+// - a geth `dump` or anvil `--dump-state` file, accounts under "accounts"
+// - a bare map of address to account
+//
+// Each account may have "balance", "nonce", "code" and "storage". Numbers can be
+// JSON numbers, decimal strings or hex strings, addresses and slots may omit 0x.
+pub fn load_state_dump(path: &str) -> Result<BTreeMap<H160, MemoryAccount>, io::Error> {
+    let contents = fs::read_to_string(path)?;
+    let root: Value = serde_json::from_str(&contents)
+        .map_err(|e| invalid_dump(path, e.to_string()))?;
+
+    let accounts = root.get("alloc")
+        .or_else(|| root.get("accounts"))
+        .unwrap_or(&root)
+        .as_object()
+        .ok_or_else(|| invalid_dump(path, "expected an object of accounts".to_string()))?;
+
+    accounts.iter()
+        .map(|(address, account)| {
+            let account = account.as_object()
+                .ok_or_else(|| invalid_dump(path, format!("account {} is not an object", address)))?;
+            let parsed = parse_address(address)
+                .and_then(|address| Ok((address, parse_account(account)?)))
+                .map_err(|e| invalid_dump(path, format!("account {}: {}", address, e)))?;
+            Ok(parsed)
+        })
+        .collect()
+}
+
+fn parse_account(account: &Map<String, Value>) -> Result<MemoryAccount, String> {
+    let balance = account.get("balance").map(parse_quantity).transpose()?.unwrap_or_default();
+    let nonce = account.get("nonce").map(parse_quantity).transpose()?.unwrap_or_default();
+
+    let code = match account.get("code") {
+        Some(Value::String(code)) => hex::decode(strip_0x(code)).map_err(|e| format!("invalid code: {}", e))?,
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => return Err(format!("invalid code {}", other)),
+    };
+
+    let mut storage = BTreeMap::new();
+    if let Some(slots) = account.get("storage").and_then(Value::as_object) {
+        for (slot, value) in slots {
+            let value = value.as_str().ok_or_else(|| format!("storage value of slot {} is not a string", slot))?;
+            let value = parse_word(value)?;
+            // Zero slots are the same as missing ones, don't keep them around
+            if value != H256::zero() {
+                storage.insert(parse_word(slot)?, value);
+            }
+        }
+    }
+
+    Ok(MemoryAccount { nonce, balance, storage, code })
+}
+
+fn parse_address(input: &str) -> Result<H160, String> {
+    let bytes = hex::decode(strip_0x(input)).map_err(|e| format!("invalid address: {}", e))?;
+    if bytes.len() != 20 {
+        return Err(format!("invalid address length {}", bytes.len()));
+    }
+    Ok(H160::from_slice(&bytes))
+}
+
+// Balances and nonces show up as 1, "1", "0x1" depending on the tool that wrote the dump
+fn parse_quantity(value: &Value) -> Result<U256, String> {
+    match value {
+        Value::Number(number) => number.as_u64()
+            .map(U256::from)
+            .ok_or_else(|| format!("invalid quantity {}", number)),
+        Value::String(s) => match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some("") => Ok(U256::zero()),
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| format!("invalid quantity {}: {:?}", s, e)),
+            None => U256::from_dec_str(s).map_err(|e| format!("invalid quantity {}: {:?}", s, e)),
+        },
+        other => Err(format!("invalid quantity {}", other)),
+    }
+}
+
+// Storage slots and values may be shortened, "0x1" is the word with value 1
+fn parse_word(input: &str) -> Result<H256, String> {
+    let digits = strip_0x(input);
+    if digits.len() > 64 {
+        return Err(format!("storage word {} is longer than 32 bytes", input));
+    }
+    let padded = format!("{:0>64}", digits);
+    let bytes = hex::decode(padded).map_err(|e| format!("invalid storage word {}: {}", input, e))?;
+    Ok(H256::from_slice(&bytes))
+}
+
+fn strip_0x(input: &str) -> &str {
+    input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input)
+}
+
+fn invalid_dump(path: &str, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid state dump {}: {}", path, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the dump to a temporary file of its own and loads it
+    fn load(name: &str, dump: &str) -> Result<BTreeMap<H160, MemoryAccount>, io::Error> {
+        let path = std::env::temp_dir().join(format!("lithevm-fork-{}-{}.json", name, std::process::id()));
+        fs::write(&path, dump).unwrap();
+        let result = load_state_dump(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    const ACCOUNT: &str = r#"{ "balance": "0x10", "nonce": 1, "code": "0x6000", "storage": { "0x0": "0x2a" } }"#;
+
+    fn check_account(accounts: &BTreeMap<H160, MemoryAccount>) {
+        let account = &accounts[&H160::repeat_byte(0x11)];
+        assert_eq!(account.balance, U256::from(16));
+        assert_eq!(account.nonce, U256::one());
+        assert_eq!(account.code, vec![0x60, 0x00]);
+        assert_eq!(account.storage[&H256::zero()], H256::from_low_u64_be(0x2a));
+    }
+
+    #[test]
+    fn loads_a_genesis_alloc() {
+        let dump = format!(r#"{{ "config": {{}}, "alloc": {{ "0x1111111111111111111111111111111111111111": {} }} }}"#, ACCOUNT);
+        check_account(&load("alloc", &dump).unwrap());
+    }
+
+    #[test]
+    fn loads_a_dump_with_accounts() {
+        let dump = format!(r#"{{ "root": "0x00", "accounts": {{ "0x1111111111111111111111111111111111111111": {} }} }}"#, ACCOUNT);
+        check_account(&load("accounts", &dump).unwrap());
+    }
+
+    #[test]
+    fn loads_a_bare_map() {
+        // Addresses may leave out the 0x
+        let dump = format!(r#"{{ "1111111111111111111111111111111111111111": {} }}"#, ACCOUNT);
+        check_account(&load("bare", &dump).unwrap());
+    }
+
+    #[test]
+    fn reads_every_number_format() {
+        let dump = r#"{
+            "0x0000000000000000000000000000000000000001": { "balance": "0xde0b6b3a7640000", "nonce": "0x" },
+            "0x0000000000000000000000000000000000000002": { "balance": "1000000000000000000", "nonce": "7" },
+            "0x0000000000000000000000000000000000000003": { "balance": 1000000000000000000, "code": null },
+            "0x0000000000000000000000000000000000000004": {
+                "storage": { "0x1": "0xff", "02": "0x0", "0x0000000000000000000000000000000000000000000000000000000000000003": "1" }
+            }
+        }"#;
+        let accounts = load("numbers", dump).unwrap();
+        let account = |n: u64| &accounts[&H160::from_low_u64_be(n)];
+        for n in 1..=3 {
+            assert_eq!(account(n).balance, U256::exp10(18));
+        }
+        assert_eq!(account(1).nonce, U256::zero());
+        assert_eq!(account(2).nonce, U256::from(7));
+        assert!(account(3).code.is_empty());
+
+        // Shortened slots and values are left padded, zero values are dropped
+        let storage = &account(4).storage;
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage[&H256::from_low_u64_be(1)], H256::from_low_u64_be(0xff));
+        assert_eq!(storage[&H256::from_low_u64_be(3)], H256::from_low_u64_be(1));
+    }
+
+    #[test]
+    fn reports_malformed_dumps() {
+        let error = |name: &str, dump: &str| load(name, dump).unwrap_err().to_string();
+        assert!(error("short", r#"{ "0x1234": {} }"#).contains("invalid address length 2"));
+        assert!(error("balance", r#"{ "0x0000000000000000000000000000000000000001": { "balance": "0xzz" } }"#).contains("invalid quantity 0xzz"));
+        assert!(error("word", &format!(r#"{{ "0x0000000000000000000000000000000000000001": {{ "storage": {{ "0x{}": "0x1" }} }} }}"#, "1".repeat(65)))
+            .contains("longer than 32 bytes"));
+        assert!(error("list", "[]").contains("expected an object of accounts"));
+    }
+}
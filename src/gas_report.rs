@@ -0,0 +1,43 @@
+use std::collections::BTreeMap;
+
+// Gas used by successful transactions, summarized like hardhat-gas-reporter does
+#[derive(Default)]
+pub struct GasReport {
+    calls: BTreeMap<(String, String), Vec<u64>>,
+    deployments: BTreeMap<String, Vec<u64>>,
+}
+
+impl GasReport {
+    pub fn record_call(&mut self, contract_name: &str, function_name: &str, gas_used: u64) {
+        self.calls.entry((contract_name.to_string(), function_name.to_string())).or_default().push(gas_used);
+    }
+
+    pub fn record_deployment(&mut self, contract_name: &str, gas_used: u64) {
+        self.deployments.entry(contract_name.to_string()).or_default().push(gas_used);
+    }
+
+    pub fn print(&self) {
+        println!("\n*** Gas report ***");
+        if self.calls.is_empty() && self.deployments.is_empty() {
+            println!("No successful transactions yet");
+            return;
+        }
+
+        let rows = self.calls.iter()
+            .map(|((contract_name, function_name), gas)| (contract_name.as_str(), function_name.as_str(), gas))
+            .chain(self.deployments.iter().map(|(contract_name, gas)| (contract_name.as_str(), "(deployment)", gas)))
+            .collect::<Vec<_>>();
+        let contract_width = rows.iter().map(|(contract_name, _, _)| contract_name.len()).max().unwrap_or(0).max("Contract".len());
+        let function_width = rows.iter().map(|(_, function_name, _)| function_name.len()).max().unwrap_or(0).max("Function".len());
+
+        println!("{:<cw$}  {:<fw$}  {:>10}  {:>10}  {:>10}  {:>6}", "Contract", "Function", "Min", "Avg", "Max", "Calls",
+            cw = contract_width, fw = function_width);
+        for (contract_name, function_name, gas) in rows {
+            let min = gas.iter().min().copied().unwrap_or(0);
+            let max = gas.iter().max().copied().unwrap_or(0);
+            let avg = gas.iter().sum::<u64>() / gas.len().max(1) as u64;
+            println!("{:<cw$}  {:<fw$}  {:>10}  {:>10}  {:>10}  {:>6}", contract_name, function_name, min, avg, max, gas.len(),
+                cw = contract_width, fw = function_width);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use std::fs;
+use std::io;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Hardfork {
+    Frontier,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+}
+
+impl Hardfork {
+    pub fn config(self) -> evm::Config {
+        match self {
+            Hardfork::Frontier => evm::Config::frontier(),
+            Hardfork::Istanbul => evm::Config::istanbul(),
+            Hardfork::Berlin => evm::Config::berlin(),
+            Hardfork::London => evm::Config::london(),
+            Hardfork::Merge => evm::Config::merge(),
+            Hardfork::Shanghai => evm::Config::shanghai(),
+        }
+    }
+}
+
+// Sets the fields of the config named in a JSON file, e.g. {"gas_sload_cold": 0, "has_push0": false}.
+// Field names are those of evm::Config, fields that are not in the file keep the hardfork's value.
+pub fn apply_overrides(config: &mut evm::Config, path: &str) -> Result<(), io::Error> {
+    let contents = fs::read_to_string(path)?;
+    let overrides: Map<String, Value> = serde_json::from_str(&contents)
+        .map_err(|e| invalid_config(path, e.to_string()))?;
+
+    for (field, value) in &overrides {
+        set_field(config, field, value).map_err(|e| invalid_config(path, format!("{}: {}", field, e)))?;
+    }
+    Ok(())
+}
+
+// Matches a field name to the config field and parses the value as the field's type
+macro_rules! config_fields {
+    ($config:ident, $field:ident, $value:ident, { $($name:ident: $parse:ident),* $(,)? }) => {
+        match $field {
+            $(stringify!($name) => $config.$name = $parse($value)?,)*
+            _ => return Err("unknown config field".to_string()),
+        }
+    };
+}
+
+fn set_field(config: &mut evm::Config, field: &str, value: &Value) -> Result<(), String> {
+    config_fields!(config, field, value, {
+        gas_ext_code: parse_u64,
+        gas_ext_code_hash: parse_u64,
+        gas_sstore_set: parse_u64,
+        gas_sstore_reset: parse_u64,
+        refund_sstore_clears: parse_i64,
+        max_refund_quotient: parse_u64,
+        gas_balance: parse_u64,
+        gas_sload: parse_u64,
+        gas_sload_cold: parse_u64,
+        gas_suicide: parse_u64,
+        gas_suicide_new_account: parse_u64,
+        gas_call: parse_u64,
+        gas_expbyte: parse_u64,
+        gas_transaction_create: parse_u64,
+        gas_transaction_call: parse_u64,
+        gas_transaction_zero_data: parse_u64,
+        gas_transaction_non_zero_data: parse_u64,
+        gas_access_list_address: parse_u64,
+        gas_access_list_storage_key: parse_u64,
+        gas_account_access_cold: parse_u64,
+        gas_storage_read_warm: parse_u64,
+        sstore_gas_metering: parse_bool,
+        sstore_revert_under_stipend: parse_bool,
+        increase_state_access_gas: parse_bool,
+        decrease_clears_refund: parse_bool,
+        disallow_executable_format: parse_bool,
+        warm_coinbase_address: parse_bool,
+        err_on_call_with_more_gas: parse_bool,
+        call_l64_after_gas: parse_bool,
+        empty_considered_exists: parse_bool,
+        create_increase_nonce: parse_bool,
+        stack_limit: parse_usize,
+        memory_limit: parse_usize,
+        call_stack_limit: parse_usize,
+        create_contract_limit: parse_limit,
+        max_initcode_size: parse_limit,
+        call_stipend: parse_u64,
+        has_delegate_call: parse_bool,
+        has_create2: parse_bool,
+        has_revert: parse_bool,
+        has_return_data: parse_bool,
+        has_bitwise_shifting: parse_bool,
+        has_chain_id: parse_bool,
+        has_self_balance: parse_bool,
+        has_ext_code_hash: parse_bool,
+        has_base_fee: parse_bool,
+        has_push0: parse_bool,
+        estimate: parse_bool,
+    });
+    Ok(())
+}
+
+fn parse_u64(value: &Value) -> Result<u64, String> {
+    value.as_u64().ok_or_else(|| format!("expected an unsigned number, got {}", value))
+}
+
+fn parse_i64(value: &Value) -> Result<i64, String> {
+    value.as_i64().ok_or_else(|| format!("expected a number, got {}", value))
+}
+
+fn parse_usize(value: &Value) -> Result<usize, String> {
+    parse_u64(value).and_then(|number| usize::try_from(number).map_err(|e| e.to_string()))
+}
+
+fn parse_bool(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("expected true or false, got {}", value))
+}
+
+// Size limits can be lifted with null
+fn parse_limit(value: &Value) -> Result<Option<usize>, String> {
+    match value {
+        Value::Null => Ok(None),
+        other => parse_usize(other).map(Some),
+    }
+}
+
+fn invalid_config(path: &str, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid EVM config {}: {}", path, message))
+}
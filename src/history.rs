@@ -0,0 +1,186 @@
+use ethereum_types::{Bloom, BloomInput, H160, H256, U256};
+use evm::backend::Log;
+use evm::ExitReason;
+
+use crate::access_list::AccessList;
+use crate::events;
+use crate::revert;
+use crate::ContractsData;
+
+// What a transaction did, like the receipt of a node, plus the call itself so past
+// transactions can be shown again without having kept them elsewhere
+pub struct Receipt {
+    pub transaction_hash: H256,
+    pub transaction_index: usize,
+    pub block_number: U256,
+    // Known once the block is sealed
+    pub block_hash: Option<H256>,
+    pub from: H160,
+    pub to: Option<H160>,
+    pub nonce: U256,
+    pub value: U256,
+    pub input: Vec<u8>,
+    pub gas_limit: u64,
+    // Set for successful deployments
+    pub contract_address: Option<H160>,
+    pub exit_reason: ExitReason,
+    // Return value, or the revert data of failed transactions
+    pub output: Vec<u8>,
+    pub gas_used: u64,
+    // Gas used by this and the earlier transactions of the block
+    pub cumulative_gas_used: u64,
+    pub effective_gas_price: U256,
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+    // Set for transactions sent signed
+    pub signed: Option<Signed>,
+}
+
+// The envelope of a signed transaction, kept so nodes can return it as it was sent
+pub struct Signed {
+    // 0 legacy, 1 access list, 2 dynamic fee
+    pub transaction_type: u8,
+    // None for legacy transactions signed without replay protection
+    pub chain_id: Option<U256>,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub access_list: AccessList,
+    // The y parity of typed transactions
+    pub v: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Receipt {
+    // 1 for success, 0 for failure, as in the receipts of nodes
+    pub fn status(&self) -> bool {
+        self.exit_reason.is_succeed()
+    }
+}
+
+pub fn logs_bloom(logs: &[Log]) -> Bloom {
+    let mut bloom = Bloom::default();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+    }
+    bloom
+}
+
+// Finds a receipt by transaction hash or by its number in the history
+pub fn find<'a>(receipts: &'a [Receipt], input: &str) -> Option<&'a Receipt> {
+    match input.parse::<usize>() {
+        Ok(index) => receipts.get(index),
+        Err(_) => {
+            let hash = input.strip_prefix("0x").unwrap_or(input).parse::<H256>().ok()?;
+            receipts.iter().find(|receipt| receipt.transaction_hash == hash)
+        },
+    }
+}
+
+// "Clock.poke", "deploy Clock" or the bare addresses when no contract matches
+fn describe(receipt: &Receipt, contracts: &ContractsData) -> String {
+    match receipt.to {
+        Some(to) => {
+            let contract = contracts.iter().find(|(_, contract_data)| contract_data.address == Some(to));
+            let function = contract.and_then(|(_, contract_data)| {
+                let selector = receipt.input.get(..4)?;
+                contract_data.abi.functions().find(|function| function.short_signature() == selector)
+            });
+            match (contract, function) {
+                (Some((name, _)), Some(function)) => format!("{}.{}", name, function.name),
+                (Some((name, _)), None) if receipt.input.is_empty() => format!("{} (receive)", name),
+                (Some((name, _)), None) => format!("{} (fallback)", name),
+                (None, _) => format!("{:?}", to),
+            }
+        },
+        None => {
+            let name = contracts.iter()
+                .find(|(_, contract_data)| contract_data.address.is_some() && contract_data.address == receipt.contract_address)
+                .map(|(name, _)| name.as_str())
+                .unwrap_or("contract");
+            format!("deploy {}", name)
+        },
+    }
+}
+
+// Lists the last `count` transactions, oldest first
+pub fn print_history(receipts: &[Receipt], contracts: &ContractsData, count: usize) {
+    if receipts.is_empty() {
+        println!("No transactions yet");
+        return;
+    }
+    let start = receipts.len().saturating_sub(count);
+    println!("*** Transactions ***");
+    for (i, receipt) in receipts.iter().enumerate().skip(start) {
+        println!("{}: {:?} block {} {:?} -> {} {} gas {}",
+            i,
+            receipt.transaction_hash,
+            receipt.block_number,
+            receipt.from,
+            describe(receipt, contracts),
+            if receipt.status() { "ok" } else { "FAILED" },
+            receipt.gas_used);
+    }
+}
+
+pub fn print_receipt(receipt: &Receipt, contracts: &ContractsData) {
+    println!("Transaction {:?}", receipt.transaction_hash);
+    match receipt.block_hash {
+        Some(block_hash) => println!("  block             {} ({:?}), index {}", receipt.block_number, block_hash, receipt.transaction_index),
+        None => println!("  block             {} (pending), index {}", receipt.block_number, receipt.transaction_index),
+    }
+    println!("  from              {:?}, nonce {}", receipt.from, receipt.nonce);
+    match receipt.to {
+        Some(to) => println!("  to                {:?}", to),
+        None => println!("  to                (create)"),
+    }
+    println!("  call              {}", describe(receipt, contracts));
+    println!("  value             {} wei", receipt.value);
+    println!("  input             0x{}", hex::encode(&receipt.input));
+    if let Some(contract_address) = receipt.contract_address {
+        println!("  contract address  {:?}", contract_address);
+    }
+    match &receipt.exit_reason {
+        ExitReason::Succeed(_) => println!("  status            1 (success)"),
+        ExitReason::Revert(_) => println!("  status            0 ({})", revert::decode_revert(&receipt.output, contracts)),
+        other => println!("  status            0 (failed: {:?})", other),
+    }
+    if !receipt.output.is_empty() && receipt.status() && receipt.to.is_some() {
+        println!("  output            0x{}", hex::encode(&receipt.output));
+    }
+    println!("  gas used          {}", receipt.gas_used);
+    println!("  cumulative gas    {}", receipt.cumulative_gas_used);
+    println!("  gas price         {} wei", receipt.effective_gas_price);
+    println!("  logs bloom        0x{}", hex::encode(receipt.logs_bloom.as_bytes()));
+    events::print_logs(&receipt.logs, contracts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bloom with the given bits set, bit 0 being the lowest bit of the last byte
+    fn bloom_with_bits(bits: &[usize]) -> Bloom {
+        let mut bytes = [0u8; 256];
+        for bit in bits {
+            bytes[255 - bit / 8] |= 1 << (bit % 8);
+        }
+        Bloom::from(bytes)
+    }
+
+    #[test]
+    fn logs_bloom_sets_three_bits_per_address_and_topic() {
+        let log = Log {
+            address: H160::repeat_byte(0x11),
+            topics: vec![H256::from_low_u64_be(0x2a)],
+            data: vec![1, 2, 3],
+        };
+        // The low 11 bits of the first three byte pairs of keccak(address), then of keccak(topic)
+        let expected = bloom_with_bits(&[704, 1028, 193, 1742, 149, 260]);
+        assert_eq!(logs_bloom(&[log]), expected);
+        assert_eq!(logs_bloom(&[]), Bloom::zero());
+    }
+}
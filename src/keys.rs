@@ -0,0 +1,125 @@
+use ethereum_types::H160;
+use hmac::{Hmac, Mac};
+use libsecp256k1::{PublicKey, SecretKey};
+use sha2::Sha512;
+use sha3::{Digest, Keccak256};
+
+use std::io;
+
+// The mnemonic anvil and hardhat derive their default accounts from
+pub const DEFAULT_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+// Parses a hex encoded secp256k1 private key
+pub fn parse_private_key(input: &str) -> Result<SecretKey, io::Error> {
+    let input = input.trim();
+    let bytes = hex::decode(input.strip_prefix("0x").unwrap_or(input))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid private key: {}", e)))?;
+    SecretKey::parse_slice(&bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid private key: {}", e)))
+}
+
+// The address is the last 20 bytes of the hash of the uncompressed public key, minus its 0x04 prefix
+pub fn address(secret_key: &SecretKey) -> H160 {
+    public_key_address(&PublicKey::from_secret_key(secret_key))
+}
+
+pub fn public_key_address(public_key: &PublicKey) -> H160 {
+    let hash = Keccak256::digest(&public_key.serialize()[1..]);
+    H160::from_slice(&hash[12..])
+}
+
+// The key of account `index` of a BIP-39 mnemonic on the standard Ethereum path
+// m/44'/60'/0'/0/index, the same accounts wallets, anvil and hardhat derive.
+// The words are not checked against the BIP-39 word list.
+pub fn derive_private_key(mnemonic: &str, index: u32) -> Result<SecretKey, io::Error> {
+    let normalized = mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2::<Hmac<Sha512>>(normalized.as_bytes(), b"mnemonic", 2048, &mut seed)
+        .map_err(io::Error::other)?;
+
+    let (mut key, mut chain_code) = hmac_sha512(b"Bitcoin seed", &seed)?;
+    for child in [HARDENED | 44, HARDENED | 60, HARDENED, 0, index] {
+        (key, chain_code) = derive_child(&key, &chain_code, child)?;
+    }
+    Ok(key)
+}
+
+const HARDENED: u32 = 0x8000_0000;
+
+// BIP-32 private parent key to private child key
+fn derive_child(key: &SecretKey, chain_code: &[u8], index: u32) -> Result<(SecretKey, [u8; 32]), io::Error> {
+    let mut data = Vec::with_capacity(37);
+    if index & HARDENED != 0 {
+        data.push(0);
+        data.extend_from_slice(&key.serialize());
+    } else {
+        data.extend_from_slice(&PublicKey::from_secret_key(key).serialize_compressed());
+    }
+    data.extend_from_slice(&index.to_be_bytes());
+
+    let (mut child, chain_code) = hmac_sha512(chain_code, &data)?;
+    child.tweak_add_assign(key).map_err(|e| io::Error::other(format!("Invalid derived key: {}", e)))?;
+    Ok((child, chain_code))
+}
+
+// Splits HMAC-SHA512 into a key and a chain code
+fn hmac_sha512(key: &[u8], data: &[u8]) -> Result<(SecretKey, [u8; 32]), io::Error> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).map_err(io::Error::other)?;
+    mac.update(data);
+    let output = mac.finalize().into_bytes();
+    let secret_key = SecretKey::parse_slice(&output[..32])
+        .map_err(|e| io::Error::other(format!("Invalid derived key: {}", e)))?;
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&output[32..]);
+    Ok((secret_key, chain_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address_of(hex: &str) -> H160 {
+        hex.parse().unwrap()
+    }
+
+    #[test]
+    fn derives_the_default_anvil_accounts() {
+        let first = derive_private_key(DEFAULT_MNEMONIC, 0).unwrap();
+        assert_eq!(hex::encode(first.serialize()), "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80");
+        assert_eq!(address(&first), address_of("f39fd6e51aad88f6f4ce6ab8827279cfffb92266"));
+        let second = derive_private_key(DEFAULT_MNEMONIC, 1).unwrap();
+        assert_eq!(address(&second), address_of("70997970c51812dc3a010c7d01b50e0d17dc79c8"));
+    }
+
+    #[test]
+    fn mnemonic_whitespace_is_normalized() {
+        let spaced = format!("  {}\n", DEFAULT_MNEMONIC.replace(' ', "  "));
+        assert_eq!(address(&derive_private_key(&spaced, 0).unwrap()), address_of("f39fd6e51aad88f6f4ce6ab8827279cfffb92266"));
+    }
+
+    // Test vector 1 of BIP-32
+    #[test]
+    fn derives_bip_32_children() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let (master, chain_code) = hmac_sha512(b"Bitcoin seed", &seed).unwrap();
+        assert_eq!(hex::encode(master.serialize()), "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35");
+        assert_eq!(hex::encode(chain_code), "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508");
+
+        // m/0'
+        let (hardened, chain_code) = derive_child(&master, &chain_code, HARDENED).unwrap();
+        assert_eq!(hex::encode(hardened.serialize()), "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea");
+        // m/0'/1
+        let (normal, _) = derive_child(&hardened, &chain_code, 1).unwrap();
+        assert_eq!(hex::encode(normal.serialize()), "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368");
+    }
+
+    #[test]
+    fn parses_private_keys_with_or_without_prefix() {
+        let key = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let expected = address_of("f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert_eq!(address(&parse_private_key(key).unwrap()), expected);
+        assert_eq!(address(&parse_private_key(&format!(" 0x{} ", key)).unwrap()), expected);
+        assert!(parse_private_key("0x1234").is_err());
+        assert!(parse_private_key("not hex").is_err());
+    }
+}
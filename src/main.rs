@@ -1,48 +1,94 @@
-use dialoguer::Input;
-use ethereum_types::{H160, U256};
-use evm::backend::MemoryBackend;
-use evm::executor::stack::{StackExecutor, MemoryStackState, StackSubstateMetadata};
+mod abi_format;
+mod abi_parse;
+mod access_list;
+mod block;
+mod call_tree;
+mod chain;
+mod cli;
+mod events;
+mod fork;
+mod gas_report;
+mod hardfork;
+mod history;
+mod keys;
+mod natives;
+mod precompiles;
+mod prompt;
+mod raw_transaction;
+mod revert;
+mod rpc;
+mod scenario;
+mod state;
+mod tracer;
+
+use ethereum_types::{H160, H256, U256};
+use evm::backend::MemoryAccount;
 use evm::ExitReason;
 use rlp::RlpStream;
 use sha3::{Digest, Keccak256};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 use std::process::{Command, exit};
 use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use ethabi::Contract;
 use ethabi::param_type::ParamType;
+use access_list::AccessList;
+use chain::{Chain, Outcome, Transaction};
+use clap::Parser;
+use cli::{Cli, Command as CliCommand};
+use hardfork::Hardfork;
+use precompiles::Precompiles;
+use tracer::Tracing;
+use prompt::Answer;
 use std::path::PathBuf;
 
 struct ContractData {
     address: Option<H160>,
     abi: Contract,
+    bytecode: Vec<u8>,
+    // ethabi drops the constructor's state mutability, so it is read from the ABI file
+    payable_constructor: bool,
 }
 
+#[derive(Clone)]
 struct Account {
     address: H160,
-    nonce: U256,
+    // Unknown for a deployer restored from a state file without --deployer-key
+    secret_key: Option<libsecp256k1::SecretKey>,
 }
 type ContractsData = HashMap<String, ContractData>;
 
 fn parse_abi(abi_path: &str) -> Result<Contract, io::Error> {
     let path = PathBuf::from(abi_path);
     let file = fs::File::open(path)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?; // Convert std::io::Error to io::Error if needed
+        .map_err(|e| io::Error::other(e.to_string()))?; // Convert std::io::Error to io::Error if needed
 
     let contract = Contract::load(file)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?; // Convert ethabi::Error to io::Error
+        .map_err(|e| io::Error::other(e.to_string()))?; // Convert ethabi::Error to io::Error
 
     Ok(contract)
 }
 
+fn has_payable_constructor(abi_path: &str) -> Result<bool, io::Error> {
+    let abi: serde_json::Value = serde_json::from_str(&fs::read_to_string(abi_path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let entries = abi.as_array().map(Vec::as_slice).unwrap_or_default();
+    Ok(entries.iter().any(|entry| {
+        entry["type"] == "constructor"
+            // Compilers before 0.4.16 only wrote the payable flag
+            && (entry["stateMutability"] == "payable" || entry["payable"] == true)
+    }))
+}
+
 fn compute_contract_address(sender: H160, nonce: U256) -> H160 {
     let mut stream = RlpStream::new_list(2);
     stream.append(&sender);
     stream.append(&nonce);
-    let hash = Keccak256::digest(&stream.out());
+    let hash = Keccak256::digest(stream.out());
     H160::from_slice(&hash[12..])
 }
 
@@ -73,25 +119,285 @@ fn collect_contract_names(contracts_dir: &str) -> Result<Vec<String>, io::Error>
     Ok(contract_names)
 }
 
-fn choose_contract(contracts: &HashMap<String, ContractData>) -> Result<String, io::Error> {
+// What was picked in the top level menu
+enum MenuChoice {
+    Contract(String),
+    // A session command and its arguments, like `save state.json`
+    Command(Vec<String>),
+}
+
+fn choose_contract(contracts: &HashMap<String, ContractData>) -> Result<Answer<MenuChoice>, io::Error> {
     println!("\nAvailable contracts:");
     for (i, name) in contracts.keys().enumerate() {
         println!("{}: {}", i + 1, name); // Display index starting from 1
     }
 
-    let chosen_index: usize = Input::new()
-    .with_prompt("Choose a contract by number")
-    .interact_text()
-    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    loop {
+        let input = match prompt::ask("Choose a contract by number, or type a command ('help' lists them)", false)? {
+            Answer::Value(input) => input,
+            Answer::Back => return Ok(Answer::Back),
+            Answer::Quit => return Ok(Answer::Quit),
+        };
+        match input.trim().parse::<usize>() {
+            Ok(number) if (1..=contracts.len()).contains(&number) => {
+                let chosen_name = contracts.keys().nth(number - 1)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid contract number"))?;
+                return Ok(Answer::Value(MenuChoice::Contract(chosen_name.to_string())));
+            },
+            Ok(_) => eprintln!("Please enter a number between 1 and {}, a command, 'back' or 'quit'", contracts.len()),
+            Err(_) => {
+                let words = input.split_whitespace().map(str::to_string).collect();
+                return Ok(Answer::Value(MenuChoice::Command(words)));
+            },
+        }
+    }
+}
 
-    if let Some(chosen_name) = contracts.keys().nth(chosen_index - 1) { // Subtract 1 to get the correct index
-        Ok(chosen_name.to_string())
-    } else {
-        Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid contract number"))
+// Runs a command typed in the top level menu. Failures are reported and the session goes on.
+fn run_command(
+    words: &[String],
+    chain: &mut Chain,
+    contracts_data: &ContractsData,
+    deployer: &Account,
+    accounts: &[Account],
+    sender: &mut H160,
+    state_path: Option<&str>,
+) {
+    match words.first().map(String::as_str) {
+        Some("help") => {
+            println!("Commands:");
+            println!("  accounts      list the funded accounts with their balances and keys");
+            println!("  sender [n|address]  send the following transactions from account n or the address");
+            println!("  send <to> <value> [0xdata]  send ether and optional calldata to an address, account number or contract,");
+            println!("                reaching its receive() or fallback()");
+            println!("  sendraw <0xtransaction>  run a signed legacy, EIP-2930 or EIP-1559 transaction, as eth_sendRawTransaction does");
+            println!("  save [file]   write the world state to the file, or to the --state file");
+            println!("  trace on|off  record and print the opcode trace of every transaction");
+            println!("  trace show    print the trace of the last traced transaction");
+            println!("  trace json <file>  write that trace as geth structLogs JSON");
+            println!("  calltrace on|off|show|json <file>  the same for the tree of nested calls");
+            println!("  gas           show min/avg/max gas per function and deployment so far");
+            println!("  gaslimit <n>  set the gas limit of the following transactions");
+            println!("  gasprice <value>  set their gas price, in wei or with a unit like '2 gwei'");
+            println!("  accesslist    show the access list of the next transaction");
+            println!("  accesslist add <address|contract|n> [slot ...]  add an address and storage slots to it");
+            println!("  accesslist create [hash|n]  run a past transaction, the last by default, again on the current state,");
+            println!("                show the addresses and slots it touches and use them as the next access list");
+            println!("  accesslist clear  send the next transaction without an access list");
+            println!("  history [n]   list the last n transactions (default 20), or all with 'history all'");
+            println!("  receipt [hash|n]  show the receipt of a transaction, by hash or number in the history, the last by default");
+            println!("  block         show the latest and the pending block");
+            println!("  mine [n]      seal the pending block, or n blocks");
+            println!("  warp <timestamp|+seconds>  set the timestamp of the pending block");
+            println!("  coinbase [n|address]  set the address receiving the fee tips of the following blocks");
+            println!("  prevrandao [0xvalue]  set PREVRANDAO of the pending block");
+            println!("  basefee [value]  set the base fee of the pending block");
+            println!("  automine on|off  seal a block after every transaction, or only on 'mine'");
+            println!("  help          show this list");
+        },
+        Some("save") => {
+            let Some(path) = words.get(1).map(String::as_str).or(state_path) else {
+                eprintln!("No file given and no --state file configured");
+                return;
+            };
+            let snapshot = state::Snapshot::take(chain, deployer.address, contracts_data);
+            match state::save(path, &snapshot) {
+                Ok(()) => println!("State saved to {}", path),
+                Err(e) => eprintln!("Failed to save state to {}: {}", path, e),
+            }
+        },
+        Some("gas") => chain.gas_report.print(),
+        Some("accounts") => print_accounts(chain, accounts, *sender),
+        Some("send") => match (words.get(1), words.get(2)) {
+            (Some(to), Some(value)) => {
+                // Values with a unit arrive as two words, e.g. "1 ether"
+                let (value, data) = match words.get(3) {
+                    Some(unit) if !unit.starts_with("0x") => (format!("{} {}", value, unit), words.get(4)),
+                    _ => (value.clone(), words.get(3)),
+                };
+                let result = parse_recipient(to, contracts_data, accounts).and_then(|to| {
+                    let value = abi_parse::parse_value(Some(&value))?;
+                    let data = parse_calldata(data.map(String::as_str))?;
+                    send_transaction(chain, contracts_data, *sender, to, value, data)
+                });
+                match result {
+                    Ok(output) if output.is_empty() => println!("Transaction succeeded"),
+                    Ok(output) => println!("Transaction succeeded, returned 0x{}", hex::encode(output)),
+                    Err(e) => eprintln!("{}", e),
+                }
+            },
+            _ => eprintln!("Usage: send <address, account number or contract> <value> [0xcalldata]"),
+        },
+        Some("sendraw") => match words.get(1) {
+            Some(input) => match parse_calldata(Some(input)).and_then(|raw| send_raw_transaction(chain, contracts_data, &raw)) {
+                Ok(output) if output.is_empty() => println!("Transaction succeeded"),
+                Ok(output) => println!("Transaction succeeded, returned 0x{}", hex::encode(output)),
+                Err(e) => eprintln!("{}", e),
+            },
+            None => eprintln!("Usage: sendraw <0x signed transaction>"),
+        },
+        Some("sender") => match words.get(1) {
+            Some(input) => match parse_sender(input, accounts) {
+                Ok(address) => {
+                    *sender = address;
+                    println!("Sending from {:?}", address);
+                },
+                Err(e) => eprintln!("Invalid sender: {}", e),
+            },
+            None => println!("Sending from {:?}", sender),
+        },
+        Some("gaslimit") => match words.get(1).map(|limit| limit.parse::<u64>()) {
+            Some(Ok(limit)) => {
+                chain.gas_limit = limit;
+                println!("Gas limit set to {}", limit);
+            },
+            Some(Err(e)) => eprintln!("Invalid gas limit: {}", e),
+            None => println!("Gas limit is {}, block gas limit {}", chain.gas_limit, chain.vicinity.block_gas_limit),
+        },
+        Some("gasprice") => match words.get(1..).filter(|rest| !rest.is_empty()) {
+            Some(rest) => match abi_parse::parse_value(Some(&rest.join(" "))) {
+                Ok(price) => {
+                    chain.gas_price = price;
+                    println!("Gas price set to {} wei", price);
+                },
+                Err(e) => eprintln!("Invalid gas price: {}", e),
+            },
+            None => println!("Gas price is {} wei", chain.gas_price),
+        },
+        Some("accesslist") => run_access_list_command(&words[1..], chain, contracts_data, accounts),
+        Some("history") => match words.get(1).map(String::as_str) {
+            Some("all") => history::print_history(&chain.receipts, contracts_data, usize::MAX),
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) => history::print_history(&chain.receipts, contracts_data, count),
+                Err(e) => eprintln!("Invalid count: {}", e),
+            },
+            None => history::print_history(&chain.receipts, contracts_data, 20),
+        },
+        Some("receipt") => match words.get(1) {
+            Some(input) => match history::find(&chain.receipts, input) {
+                Some(receipt) => history::print_receipt(receipt, contracts_data),
+                None => eprintln!("No transaction {} in the history", input),
+            },
+            None => match chain.receipts.last() {
+                Some(receipt) => history::print_receipt(receipt, contracts_data),
+                None => eprintln!("No transactions yet"),
+            },
+        },
+        Some("block") => print_blocks(chain),
+        Some("mine") => match words.get(1).map_or(Ok(1), |count| count.parse::<u64>()) {
+            Ok(count) => {
+                chain.mine(count);
+                let latest = chain.latest_block();
+                println!("Mined {} block(s), latest is {} ({:?}) at timestamp {}", count, latest.number, latest.hash, latest.timestamp);
+            },
+            Err(e) => eprintln!("Invalid block count: {}", e),
+        },
+        Some("warp") => match words.get(1) {
+            Some(input) => match block::parse_timestamp(input, chain.vicinity.block_timestamp)
+                .and_then(|timestamp| chain.warp(timestamp)) {
+                Ok(()) => println!("Pending block {} now has timestamp {}", chain.vicinity.block_number, chain.vicinity.block_timestamp),
+                Err(e) => eprintln!("Cannot warp: {}", e),
+            },
+            None => eprintln!("Usage: warp <timestamp|+seconds>"),
+        },
+        Some("coinbase") => match words.get(1) {
+            Some(input) => match parse_sender(input, accounts) {
+                Ok(address) => {
+                    chain.vicinity.block_coinbase = address;
+                    println!("Coinbase set to {:?}", address);
+                },
+                Err(e) => eprintln!("Invalid coinbase: {}", e),
+            },
+            None => println!("Coinbase is {:?}", chain.vicinity.block_coinbase),
+        },
+        Some("prevrandao") => match words.get(1) {
+            Some(input) => match block::parse_prevrandao(input) {
+                Ok(value) => {
+                    chain.vicinity.block_randomness = Some(value);
+                    println!("PREVRANDAO of the pending block set to {:?}", value);
+                },
+                Err(e) => eprintln!("Invalid prevrandao: {}", e),
+            },
+            None => match chain.vicinity.block_randomness {
+                Some(value) => println!("PREVRANDAO of the pending block is {:?}", value),
+                None => println!("Before the merge there is no PREVRANDAO, DIFFICULTY is {}", chain.vicinity.block_difficulty),
+            },
+        },
+        Some("basefee") => match words.get(1..).filter(|rest| !rest.is_empty()) {
+            Some(rest) => match abi_parse::parse_value(Some(&rest.join(" "))) {
+                Ok(base_fee) => {
+                    chain.vicinity.block_base_fee_per_gas = base_fee;
+                    println!("Base fee of the pending block set to {} wei", base_fee);
+                },
+                Err(e) => eprintln!("Invalid base fee: {}", e),
+            },
+            None => println!("Base fee of the pending block is {} wei", chain.vicinity.block_base_fee_per_gas),
+        },
+        Some("automine") => match words.get(1).map(String::as_str) {
+            Some("on") => {
+                chain.automine = true;
+                // Transactions waiting in the pending block go into it right away
+                if !chain.pending_transactions.is_empty() {
+                    chain.mine(1);
+                }
+                println!("Automine on");
+            },
+            Some("off") => {
+                chain.automine = false;
+                println!("Automine off, transactions stay in the pending block until 'mine'");
+            },
+            _ => eprintln!("Usage: automine on|off"),
+        },
+        Some("trace") => match (words.get(1).map(String::as_str), words.get(2)) {
+            (Some("on"), _) => {
+                chain.tracing = Tracing::Print;
+                println!("Tracing on");
+            },
+            (Some("off"), _) => {
+                chain.tracing = Tracing::Off;
+                println!("Tracing off");
+            },
+            (Some("show"), _) => match &chain.last_trace {
+                Some(trace) => tracer::print_trace(trace),
+                None => eprintln!("No transaction was traced yet, use 'trace on' first"),
+            },
+            (Some("json"), Some(path)) => match &chain.last_trace {
+                Some(trace) => match tracer::write_struct_logs(path, trace) {
+                    Ok(()) => println!("Trace written to {}", path),
+                    Err(e) => eprintln!("Failed to write trace to {}: {}", path, e),
+                },
+                None => eprintln!("No transaction was traced yet, use 'trace on' first"),
+            },
+            _ => eprintln!("Usage: trace on|off|show|json <file>"),
+        },
+        Some("calltrace") => match (words.get(1).map(String::as_str), words.get(2)) {
+            (Some("on"), _) => {
+                chain.call_tracing = Tracing::Print;
+                println!("Call tracing on");
+            },
+            (Some("off"), _) => {
+                chain.call_tracing = Tracing::Off;
+                println!("Call tracing off");
+            },
+            (Some("show"), _) => match chain.last_trace.as_ref().and_then(|trace| trace.call_tree.as_ref()) {
+                Some(root) => call_tree::print_call_tree(root, contracts_data),
+                None => eprintln!("No transaction was traced yet, use 'calltrace on' first"),
+            },
+            (Some("json"), Some(path)) => match chain.last_trace.as_ref().and_then(|trace| trace.call_tree.as_ref()) {
+                Some(root) => match call_tree::write_call_tree(path, root, contracts_data) {
+                    Ok(()) => println!("Call tree written to {}", path),
+                    Err(e) => eprintln!("Failed to write call tree to {}: {}", path, e),
+                },
+                None => eprintln!("No transaction was traced yet, use 'calltrace on' first"),
+            },
+            _ => eprintln!("Usage: calltrace on|off|show|json <file>"),
+        },
+        Some(other) => eprintln!("Unknown command '{}', type 'help' to list the commands", other),
+        None => {},
     }
 }
 
-fn choose_function(abi: &ethabi::Contract) -> Result<(String, bool, Vec<ParamType>), io::Error> {
+fn choose_function(abi: &ethabi::Contract) -> Result<Answer<(String, bool, Vec<ParamType>)>, io::Error> {
     
     // Collect function names, check if they are getters, and get their return types
     let functions_info: Vec<(String, bool, Vec<ParamType>)> = abi.functions.iter()
@@ -107,37 +413,50 @@ fn choose_function(abi: &ethabi::Contract) -> Result<(String, bool, Vec<ParamTyp
         })
         .collect();
 
-    
+    if functions_info.is_empty() {
+        println!("This contract has no functions");
+        return Ok(Answer::Back);
+    }
+
     for (i, (name, is_getter, return_types)) in functions_info.iter().enumerate() {
-        let getter_marker = if *is_getter { " (getter)" } else { "" };
+        let is_payable = abi.functions_by_name(name).is_ok_and(|functions| {
+            functions.iter().any(|function| function.state_mutability == ethabi::StateMutability::Payable)
+        });
+        let marker = match (*is_getter, is_payable) {
+            (true, _) => " (getter)",
+            (false, true) => " (payable)",
+            (false, false) => "",
+        };
         let return_types_str = return_types.iter()
             .map(|param_type| format!("{:?}", param_type))
             .collect::<Vec<_>>()
             .join(", ");
-        println!("{}: {}{} -> [{}]", i + 1, name, getter_marker, return_types_str);
+        println!("{}: {}{} -> [{}]", i + 1, name, marker, return_types_str);
     }
-    
-    let chosen_index: usize = Input::new()
-        .with_prompt("Choose a function by number")
-        .interact_text()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-
 
-    if let Some(chosen_name) = functions_info.get(chosen_index - 1) { // Subtract 1 to get the correct index
-        Ok(chosen_name.clone())
-    } else {
-        Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid function number"))
+    match prompt::ask_index("Choose a function by number", functions_info.len())? {
+        Answer::Value(chosen_index) => Ok(Answer::Value(functions_info[chosen_index].clone())),
+        Answer::Back => Ok(Answer::Back),
+        Answer::Quit => Ok(Answer::Quit),
     }
 }
 
-fn call_contract_function<'a>(
-    executor: &mut StackExecutor<'a, 'a, MemoryStackState<'a, 'a, MemoryBackend<'a>>, ()>,
-    contract_data: &ContractData,
+fn call_contract_function(
+    chain: &mut Chain,
+    contracts_data: &ContractsData,
+    contract_name: &str,
     function_name: &str,
     encoded_inputs: Vec<u8>,
     caller_address: H160,   // Caller's address
+    value: U256,            // Wei sent with the call
 ) -> Result<Vec<ethabi::Token>, io::Error> {
-    
+
+    let contract_data = contracts_data
+        .get(contract_name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Contract not found"))?;
+    let contract_address = contract_data.address
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Contract address not set"))?;
+
     let function = contract_data
         .abi
         .function(function_name)
@@ -154,179 +473,199 @@ fn call_contract_function<'a>(
     let data = [function_selector.to_vec(), encoded_inputs].concat();
 
     println!("stack data: {:?}", data);
-    // Execute the function call
-    let (exit_reason, output) = executor.transact_call(
-        caller_address,
-        contract_data.address.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Contract address not set"))?,
-        U256::zero(), // value sent with the call
+    let transaction = Transaction {
+        caller: caller_address,
+        to: Some(contract_address),
+        value,
         data,
-        u64::MAX, // gas_limit
-        Vec::new(), // access_list
-    );
-    match exit_reason {
+        gas_limit: None,
+        gas_price: None,
+        access_list: take_access_list(chain),
+        nonce: None,
+        hash: None,
+    };
+    // View and pure functions are read like eth_call, anything else is sent as a transaction
+    let read_only = matches!(function.state_mutability, ethabi::StateMutability::View | ethabi::StateMutability::Pure);
+    let outcome = if read_only {
+        println!("Read-only call, nothing will be committed");
+        chain.call(transaction)?
+    } else {
+        chain.transact(transaction)?
+    };
+    print_traces(chain, contracts_data);
+    events::print_logs(&outcome.logs, contracts_data);
+    outcome.print_inclusion();
+    outcome.print_gas();
+
+    match outcome.exit_reason {
         ExitReason::Succeed(_) => {
+            // Only transactions count, view and pure reads are free when called off-chain
+            if !read_only {
+                chain.gas_report.record_call(contract_name, function_name, outcome.gas_used);
+            }
+
             // Decode the function output if there is any
-            let decoded_output = if !output.is_empty() {
-                function.decode_output(&output).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+            let decoded_output = if !outcome.output.is_empty() {
+                function.decode_output(&outcome.output).map_err(|e| io::Error::other(e.to_string()))?
             } else {
                 Vec::new()
             };
-            println!("Raw output: {:?}", output);
+            println!("Raw output: {:?}", outcome.output);
             println!("Output    : {:?}", decoded_output);
             Ok(decoded_output)
         }
+        ExitReason::Revert(_) => {
+            let reason = revert::decode_revert(&outcome.output, contracts_data);
+            Err(io::Error::other(revert::Reverted(reason)))
+        }
+        ExitReason::Error(_) => {
+            Err(io::Error::other(revert::Reverted(outcome.describe_failure())))
+        }
         _ => {
-            eprintln!("Call failed: {:?}", exit_reason);
-            Err(io::Error::new(io::ErrorKind::Other, "Call failed"))
+            Err(io::Error::other(format!("Call {}", outcome.describe_failure())))
+        }
+    }
+}
+
+// Sends ether and raw calldata to any address, which for a contract runs its
+// receive() or fallback() unless the data starts with one of its selectors
+fn send_transaction(
+    chain: &mut Chain,
+    contracts_data: &ContractsData,
+    from: H160,
+    to: H160,
+    value: U256,
+    data: Vec<u8>,
+) -> Result<Vec<u8>, io::Error> {
+    println!("Sending {} and {} bytes of calldata from {:?} to {:?}", abi_format::format_ether(value), data.len(), from, to);
+    let access_list = take_access_list(chain);
+    let outcome = chain.transact(Transaction {
+        caller: from,
+        to: Some(to),
+        value,
+        data,
+        gas_limit: None,
+        gas_price: None,
+        access_list,
+        nonce: None,
+        hash: None,
+    })?;
+    report_outcome(chain, contracts_data, outcome)
+}
+
+// Decodes a signed transaction, checks it like a node would and runs it as its signer
+fn send_raw_transaction(chain: &mut Chain, contracts_data: &ContractsData, raw: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let (transaction, outcome) = raw_transaction::send(chain, raw)?;
+    match transaction.to {
+        Some(to) => println!("Signed transaction of type {} from {:?} (nonce {}) to {:?}, sending {}",
+            transaction.transaction_type, transaction.from, transaction.nonce, to, abi_format::format_ether(transaction.value)),
+        None => println!("Signed transaction of type {} from {:?} (nonce {}) deploying to {:?}",
+            transaction.transaction_type, transaction.from, transaction.nonce, compute_contract_address(transaction.from, transaction.nonce)),
+    }
+    report_outcome(chain, contracts_data, outcome)
+}
+
+fn report_outcome(chain: &Chain, contracts_data: &ContractsData, outcome: Outcome) -> Result<Vec<u8>, io::Error> {
+    print_traces(chain, contracts_data);
+    events::print_logs(&outcome.logs, contracts_data);
+    outcome.print_inclusion();
+    outcome.print_gas();
+
+    match outcome.exit_reason {
+        ExitReason::Succeed(_) => Ok(outcome.output),
+        ExitReason::Revert(_) => {
+            let reason = revert::decode_revert(&outcome.output, contracts_data);
+            Err(io::Error::other(format!("Transaction {}", reason)))
+        },
+        _ => Err(io::Error::other(format!("Transaction {}", outcome.describe_failure()))),
+    }
+}
+
+// The access list set for the next transaction, which uses it up
+fn take_access_list(chain: &mut Chain) -> AccessList {
+    let access_list = std::mem::take(&mut chain.access_list);
+    if !access_list.is_empty() {
+        let (addresses, keys) = access_list::size(&access_list);
+        println!("Sending with an access list of {} address(es) and {} storage key(s)", addresses, keys);
+    }
+    access_list
+}
+
+// A recipient given as a contract name, an address or the number of a funded account
+fn parse_recipient(input: &str, contracts_data: &ContractsData, accounts: &[Account]) -> Result<H160, io::Error> {
+    match contracts_data.get(input) {
+        Some(contract_data) => contract_data.address
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Contract {} is not deployed", input))),
+        None => parse_sender(input, accounts),
+    }
+}
+
+fn parse_calldata(input: Option<&str>) -> Result<Vec<u8>, io::Error> {
+    let input = input.unwrap_or_default().trim();
+    hex::decode(input.strip_prefix("0x").unwrap_or(input))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid calldata: {}", e)))
+}
+
+// Asks for the wei sent to a payable function or constructor, with units accepted
+fn ask_for_value() -> Result<Answer<U256>, io::Error> {
+    loop {
+        let input = match prompt::ask("Value to send [wei, or with a unit like 1 ether] (press enter for 0)", true)? {
+            Answer::Value(input) => input,
+            Answer::Back => return Ok(Answer::Back),
+            Answer::Quit => return Ok(Answer::Quit),
+        };
+        let input = input.trim();
+        match abi_parse::parse_value(if input.is_empty() { None } else { Some(input) }) {
+            Ok(value) => return Ok(Answer::Value(value)),
+            Err(e) => eprintln!("{}", e),
         }
     }
 }
 
-fn ask_for_function_inputs(params: &[ParamType], deployer_address: H160) -> Result<Vec<String>, io::Error> {
+fn ask_for_function_inputs(params: &[ParamType], deployer_address: H160) -> Result<Answer<Vec<String>>, io::Error> {
 
     let mut args = Vec::new();
     for (i, param) in params.iter().enumerate() {
-        let input = match param {
-            ParamType::Address => {
-                let default_address = format!("{:?}", deployer_address);
-                let input: String = Input::new()
-                    .with_prompt(&format!("Parameter {} [address] (press enter for default: {})", i, default_address))
-                    .allow_empty(true)
-                    .interact_text()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-                let input = if input.trim().is_empty() {
-                    default_address // Use the default deployer address
-                } else {
-                    input
-                };
-                input
-            },
-            ParamType::Uint(size) => {
-                let input: String = Input::new()
-                    .with_prompt(&format!("Parameter {} [uint{}] value (press enter for 0)", i, size))
-                    .allow_empty(true)
-                    .interact_text()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-                let input = if input.trim().is_empty() {
-                    "0".to_string() // Default value: 0
-                } else {
-                    input
-                };
-                input
-            },
-            ParamType::String => {
-                let input: String = Input::new()
-                    .with_prompt(&format!("Parameter {} [string] (press enter for empty)", i))
-                    .allow_empty(true)
-                    .interact_text()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-                let input = if input.trim().is_empty() {
-                    "".to_string() // Default value: empty string
-                } else {
-                    input
-                };
-                input
-            },
-            ParamType::Bool => {
-                let input: String = Input::new()
-                    .with_prompt(&format!("Parameter {} [boolean] (true or false, press enter for false)",i))
-                    .allow_empty(true)
-                    .interact_text()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-                let input = if input.trim().is_empty() {
-                    "false".to_string() // Default value: false
-                } else {
-                    input
-                };
-                input
-            },
-            ParamType::FixedBytes(size) => {
-                let input: String = Input::new()
-                    .with_prompt(&format!("Parameter {} [bytes32] (up to 64 hex characters, press enter for zeroed bytes)", i))
-                    .allow_empty(true)
-                    .interact_text()
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            
-                let input = if input.trim().is_empty() {
-                    "0".repeat(64) // Default value: zeroed bytes32
-                } else {
-                    let trimmed_input = input.trim();
-                    if trimmed_input.len() > size * 2 {
-                        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Input must be up to {} hex characters", size * 2)));
-                    }
-            
-                    // If the length of trimmed_input is odd, prepend a zero to make it even length.
-                    let even_length_input = if trimmed_input.len() % 2 == 1 {
-                        format!("0{}", trimmed_input)
-                    } else {
-                        trimmed_input.to_string()
-                    };
-            
-                    // Pad the input with zeros to make it fit into 32 bytes.
-                    format!("{:0<width$}", even_length_input, width = size * 2)
-                };
-            
+        let default = abi_parse::default_value(param, deployer_address);
+        let default_label = if default.is_empty() { "empty" } else { default.as_str() };
+        let prompt_text = format!("Parameter {} [{}] (press enter for default: {})", i, param, default_label);
+
+        // Keep asking until the value parses, so one typo doesn't abandon the whole call
+        loop {
+            let input = match prompt::ask(&prompt_text, true)? {
+                Answer::Value(input) => input,
+                Answer::Back => return Ok(Answer::Back),
+                Answer::Quit => return Ok(Answer::Quit),
+            };
+            let input = if input.trim().is_empty() {
+                default.clone()
+            } else {
                 input
-            },            
-            _ => {
-                println!("Unhandled parameter type: {:?}", param);
-                unimplemented!("Type not supported yet: {:?}", param);
-            },
-        };
-        args.push(input);
+            };
+            match abi_parse::parse_token(param, &input) {
+                Ok(_) => {
+                    args.push(input);
+                    break;
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        }
     }
 
-    Ok(args)
+    Ok(Answer::Value(args))
 }
 
 fn encode_function_args(params: &[ParamType], args: Vec<String>) -> Result<Vec<u8>, io::Error> {
 
-    let tokens = params.iter().zip(args.iter()).map(|(param, arg)| {
-        match param {
-            ParamType::Address => {
-                let parsed_address = arg.trim_start_matches("0x").parse::<H160>()
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
-                Ok(ethabi::Token::Address(parsed_address))
-            },
-            ParamType::Uint(_) => {
-                let parsed_uint = U256::from_dec_str(arg)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
-                Ok(ethabi::Token::Uint(parsed_uint))
-            },
-            ParamType::String => {
-                Ok(ethabi::Token::String(arg.clone()))
-            },
-            ParamType::Bool => {
-                let value = match arg.as_str() {
-                    "true" => true,
-                    "false" => false,
-                    _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid bool input")),
-                };
-                Ok(ethabi::Token::Bool(value))
-            },
-            ParamType::FixedBytes(size) if *size == 32 => {
-                if arg.len() != 64 {
-                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "FixedBytes(32) must be 64 hex characters"));
-                }
-                let mut bytes = Vec::new();
-                for i in 0..(arg.len() / 2) {
-                    let byte = u8::from_str_radix(&arg[i*2..i*2+2], 16)
-                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
-                    bytes.push(byte);
-                }
-                Ok(ethabi::Token::FixedBytes(bytes))
-            },
-            _ => unimplemented!("Type not supported yet: {:?}", param),
-        }
-    }).collect::<Result<Vec<_>, _>>()
-    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-    
+    let tokens = params.iter().zip(args.iter())
+        .map(|(param, arg)| abi_parse::parse_token(param, arg))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let encoded = ethabi::encode(&tokens);
     Ok(encoded)
 }
 
-fn compile_contracts(contracts_dir: &str, contract_names: &[String]) -> Result<(), io::Error> {
+fn compile_contracts(contracts_dir: &str, build_dir: &str, solc: &str, contract_names: &[String]) -> Result<(), io::Error> {
     for contract_name in contract_names {
         // Extract the contract base name without the .sol extension
         let contract_base_name = Path::new(contract_name)
@@ -336,7 +675,7 @@ fn compile_contracts(contracts_dir: &str, contract_names: &[String]) -> Result<(
             .to_string();
 
         // Construct the path for the contract-specific build directory
-        let contract_build_dir = format!("./build/contracts/{}", contract_base_name);
+        let contract_build_dir = format!("{}/{}", build_dir, contract_base_name);
         fs::create_dir_all(&contract_build_dir)?; // Create the contract-specific build directory
 
         // Construct the full path for the contract file
@@ -347,8 +686,8 @@ fn compile_contracts(contracts_dir: &str, contract_names: &[String]) -> Result<(
         println!("Compiling: {}", contract_file_path.display());
 
         // Run the solc command to compile the contract
-        let output = Command::new("solc")
-            .args(&[
+        let output = Command::new(solc)
+            .args([
                 "--abi",
                 "--bin",
                 contract_file_path.to_str().unwrap(),
@@ -404,167 +743,560 @@ fn compile_contracts(contracts_dir: &str, contract_names: &[String]) -> Result<(
     Ok(())
 }
 
-fn deploy_contracts<'a>(
+// Reads the compiled bytecode of a contract from the build directory
+fn load_bytecode(build_dir: &str, contract_name: &str) -> Result<Vec<u8>, io::Error> {
+    let bytecode_path = format!("{}/{}/{}.bin", build_dir, contract_name, contract_name);
+    let bytecode_path = fs::canonicalize(&bytecode_path)?;
+    let bytecode = fs::read_to_string(&bytecode_path)
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?
+        .trim_end()
+        .to_string();
+
+    // Decode the hex bytecode
+    hex::decode(bytecode)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+// Deploys one contract with already encoded constructor args and records its address
+fn deploy_contract(
+    chain: &mut Chain,
     contracts_data: &mut ContractsData,
-    executor: &mut StackExecutor<'a, 'a, MemoryStackState<'a, 'a, MemoryBackend<'a>>, ()>,
-    deployer: &mut Account,
-) -> Result<(), io::Error> {
-    
-    for (contract_name, contract_data) in contracts_data.iter_mut() {
-        println!("\nDeploying contract: {}.sol", contract_name);
-        // Load bytecode
-        let bytecode_path = format!("./build/contracts/{}/{}.bin", contract_name, contract_name);
-        let bytecode_path = fs::canonicalize(&bytecode_path)?;
-        let bytecode = fs::read_to_string(&bytecode_path)
-            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?
-            .trim_end()
-            .to_string();
+    contract_name: &str,
+    encoded_args: Vec<u8>,
+    deployer_address: H160,
+    value: U256,
+) -> Result<H160, io::Error> {
+    let mut bytecode = contracts_data.get(contract_name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown contract {}", contract_name)))?
+        .bytecode.clone();
+    // Interfaces, abstract contracts and native precompiles have nothing to deploy
+    if bytecode.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no bytecode to deploy", contract_name)));
+    }
+
+    // Append encoded args to bytecode
+    bytecode.extend(encoded_args);
+
+    // The address is derived from the sender's nonce before the create bumps it
+    let contract_address = compute_contract_address(deployer_address, chain.nonce(deployer_address));
+
+    // Deploy the contract
+    let access_list = take_access_list(chain);
+    let outcome = chain.transact(Transaction {
+        caller: deployer_address,
+        to: None,
+        value,
+        data: bytecode,
+        gas_limit: None,
+        gas_price: None,
+        access_list,
+        nonce: None,
+        hash: None,
+    })?;
+    print_traces(chain, contracts_data);
+    outcome.print_inclusion();
+    outcome.print_gas();
+
+    // Check if the transaction was successful
+    match outcome.exit_reason {
+        ExitReason::Succeed(_) => {
+            println!("Contract {} deployed at: {:?}", contract_name, contract_address);
+            chain.gas_report.record_deployment(contract_name, outcome.gas_used);
+
+            // Update the address in contract_data
+            if let Some(contract_data) = contracts_data.get_mut(contract_name) {
+                contract_data.address = Some(contract_address);
+            }
+            events::print_logs(&outcome.logs, contracts_data);
+            Ok(contract_address)
+        },
+        ExitReason::Revert(_) => {
+            let reason = revert::decode_revert(&outcome.output, contracts_data);
+            Err(io::Error::other(format!("constructor {}", reason)))
+        },
+        _ => Err(io::Error::other(format!("constructor {}", outcome.describe_failure()))),
+    }
+}
+
+// Deploys every contract that has no address yet. A contract whose prompts are
+// answered with back, or whose deployment fails, is skipped and left undeployed.
+fn deploy_contracts(
+    chain: &mut Chain,
+    contracts_data: &mut ContractsData,
+    deployer: &Account,
+) -> Result<Answer<()>, io::Error> {
 
-        // Decode the hex bytecode
-        let mut bytecode = hex::decode(bytecode)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let contract_names: Vec<String> = contracts_data.keys().cloned().collect();
+    for contract_name in contract_names {
+        // Contracts restored from a saved state keep their address
+        if let Some(address) = contracts_data[&contract_name].address {
+            if contracts_data[&contract_name].bytecode.is_empty() {
+                println!("\nNative precompile {} at: {:?}", contract_name, address);
+            } else {
+                println!("\nContract {} already deployed at: {:?}", contract_name, address);
+            }
+            continue;
+        }
+        println!("\nDeploying contract: {}.sol", contract_name);
 
         // Get constructor parameters from contract_data.abi
-        if let Some(constructor) = contract_data.abi.constructor() {
-            let params = &constructor.inputs;
+        let mut encoded_args = Vec::new();
+        if let Some(constructor) = contracts_data[&contract_name].abi.constructor() {
+            let params = constructor.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
 
             // Ask for constructor args
-            let args = ask_for_function_inputs(&params.iter().map(|p| p.kind.clone()).collect::<Vec<_>>(), deployer.address)?;
+            let args = match ask_for_function_inputs(&params, deployer.address)? {
+                Answer::Value(args) => args,
+                Answer::Back => {
+                    println!("Skipping {}", contract_name);
+                    continue;
+                },
+                Answer::Quit => return Ok(Answer::Quit),
+            };
 
             // Encode constructor args
-            let encoded_args = encode_function_args(&params.iter().map(|p| p.kind.clone()).collect::<Vec<_>>(), args)?;
+            encoded_args = match encode_function_args(&params, args) {
+                Ok(encoded_args) => encoded_args,
+                Err(e) => {
+                    eprintln!("Invalid constructor arguments, skipping {}: {}", contract_name, e);
+                    continue;
+                }
+            };
+        }
 
-            // Append encoded args to bytecode
-            bytecode.extend(encoded_args);
+        let value = if contracts_data[&contract_name].payable_constructor {
+            match ask_for_value()? {
+                Answer::Value(value) => value,
+                Answer::Back => {
+                    println!("Skipping {}", contract_name);
+                    continue;
+                },
+                Answer::Quit => return Ok(Answer::Quit),
+            }
+        } else {
+            U256::zero()
+        };
+
+        if let Err(e) = deploy_contract(chain, contracts_data, &contract_name, encoded_args, deployer.address, value) {
+            // The other contracts can still be deployed and used
+            eprintln!("Failed to deploy contract {}, skipping it: {}", contract_name, e);
         }
+    }
 
-        // Deploy the contract
-        let (exit_reason, _output) = executor.transact_create(
-            deployer.address,
-            U256::zero(), // value
-            bytecode,
-            u64::MAX, // gas_limit
-            Vec::new(), // access_list
-        );
-
-        // Check if the transaction was successful
-        if let ExitReason::Succeed(_) = exit_reason {
-            // Use sender_nonce to compute the contract address
-            let contract_address = compute_contract_address(deployer.address, deployer.nonce);
-            println!("Contract {} deployed at: {:?}", contract_name, contract_address);
+    Ok(Answer::Value(()))
+}
 
-            // Update the address in contract_data
-            contract_data.address = Some(contract_address);
+// Prints the traces of the last transaction that are switched to printing
+fn print_traces(chain: &Chain, contracts_data: &ContractsData) {
+    let Some(trace) = &chain.last_trace else {
+        return;
+    };
+    if chain.tracing == Tracing::Print {
+        tracer::print_trace(trace);
+    }
+    if chain.call_tracing == Tracing::Print {
+        if let Some(root) = &trace.call_tree {
+            call_tree::print_call_tree(root, contracts_data);
+        }
+    }
+}
+
+// Encodes literal arguments given on the command line for the given parameter types
+fn encode_cli_args(params: &[ParamType], args: &[String]) -> Result<Vec<u8>, io::Error> {
+    if params.len() != args.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Expected {} arguments, got {}", params.len(), args.len())));
+    }
+    encode_function_args(params, args.to_vec())
+}
+
+fn constructor_params(contracts_data: &ContractsData, contract_name: &str) -> Result<Vec<ParamType>, io::Error> {
+    let contract_data = contracts_data.get(contract_name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown contract {}", contract_name)))?;
+    Ok(contract_data.abi.constructor()
+        .map(|constructor| constructor.inputs.iter().map(|p| p.kind.clone()).collect())
+        .unwrap_or_default())
+}
+
+// Deploys a contract for the call subcommand, unless it is already deployed in the
+// loaded state. Returns whether the contract is available.
+fn ensure_deployed(
+    chain: &mut Chain,
+    contracts_data: &mut ContractsData,
+    contract: &str,
+    constructor_args: &[String],
+    deployer_address: H160,
+) -> Result<bool, io::Error> {
+    if contracts_data.get(contract).and_then(|contract_data| contract_data.address).is_some() {
+        return Ok(true);
+    }
+    let encoded_args = encode_cli_args(&constructor_params(contracts_data, contract)?, constructor_args)?;
+    match deploy_contract(chain, contracts_data, contract, encoded_args, deployer_address, U256::zero()) {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            eprintln!("Failed to deploy contract {}: {}", contract, e);
+            Ok(false)
+        }
+    }
+}
+
+// A sender given as an address or as the number of one of the funded accounts
+fn parse_sender(input: &str, accounts: &[Account]) -> Result<H160, io::Error> {
+    let input = input.trim();
+    if let Ok(index) = input.parse::<usize>() {
+        return accounts.get(index)
+            .map(|account| account.address)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("There is no account {}, there are {} accounts", index, accounts.len())));
+    }
+    match abi_parse::parse_token(&ParamType::Address, input)? {
+        ethabi::Token::Address(address) => Ok(address),
+        _ => unreachable!("address params always parse to address tokens"),
+    }
+}
+
+fn run_access_list_command(words: &[String], chain: &mut Chain, contracts_data: &ContractsData, accounts: &[Account]) {
+    match words.first().map(String::as_str) {
+        None => access_list::print_access_list(&chain.access_list, contracts_data),
+        Some("add") => match words.get(1) {
+            Some(address) => {
+                let entry = parse_recipient(address, contracts_data, accounts).and_then(|address| {
+                    let keys = words[2..].iter()
+                        .map(|key| access_list::parse_storage_key(key))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok((address, keys))
+                });
+                match entry {
+                    Ok((address, keys)) => {
+                        access_list::add(&mut chain.access_list, address, keys);
+                        access_list::print_access_list(&chain.access_list, contracts_data);
+                    },
+                    Err(e) => eprintln!("Invalid access list entry: {}", e),
+                }
+            },
+            None => eprintln!("Usage: accesslist add <address, contract or account number> [slot ...]"),
+        },
+        Some("clear") => {
+            chain.access_list.clear();
+            println!("The next transaction is sent without an access list");
+        },
+        Some("create") => {
+            let receipt = match words.get(1) {
+                Some(input) => history::find(&chain.receipts, input),
+                None => chain.receipts.last(),
+            };
+            let Some(receipt) = receipt else {
+                eprintln!("No such transaction in the history");
+                return;
+            };
+            let transaction = Transaction {
+                caller: receipt.from,
+                to: receipt.to,
+                value: receipt.value,
+                data: receipt.input.clone(),
+                gas_limit: Some(receipt.gas_limit),
+                gas_price: None,
+                access_list: Vec::new(),
+                nonce: None,
+                hash: None,
+            };
+            let without = match chain.simulate(transaction.clone()) {
+                Ok(outcome) => outcome.gas_used,
+                Err(e) => {
+                    eprintln!("Cannot run the transaction: {}", e);
+                    return;
+                },
+            };
+            match chain.create_access_list(transaction) {
+                Ok((created, outcome)) => {
+                    access_list::print_access_list(&created, contracts_data);
+                    if !outcome.exit_reason.is_succeed() {
+                        println!("The transaction fails on the current state: {}", match outcome.exit_reason {
+                            ExitReason::Revert(_) => revert::decode_revert(&outcome.output, contracts_data),
+                            _ => outcome.describe_failure(),
+                        });
+                    }
+                    if created.is_empty() {
+                        return;
+                    }
+                    if outcome.gas_used < without {
+                        println!("Gas used: {} with the access list, {} without, it saves {}",
+                            outcome.gas_used, without, without - outcome.gas_used);
+                    } else {
+                        println!("Gas used: {} with the access list, {} without, it costs {} more",
+                            outcome.gas_used, without, outcome.gas_used - without);
+                    }
+                    chain.access_list = created;
+                    println!("The next transaction is sent with this access list, 'accesslist clear' drops it");
+                },
+                Err(e) => eprintln!("Cannot create an access list: {}", e),
+            }
+        },
+        Some(_) => eprintln!("Usage: accesslist [add <address> [slot ...] | create [hash|n] | clear]"),
+    }
+}
+
+fn print_blocks(chain: &Chain) {
+    let latest = chain.latest_block();
+    println!("Latest block {}: {:?}", latest.number, latest.hash);
+    println!("  parent     {:?}", latest.parent_hash);
+    println!("  timestamp  {}", latest.timestamp);
+    println!("  coinbase   {:?}", latest.coinbase);
+    println!("  base fee   {} wei", latest.base_fee);
+    if let Some(prevrandao) = latest.prevrandao {
+        println!("  prevrandao {:?}", prevrandao);
+    }
+    println!("  gas used   {} of {}, {} transaction(s)", latest.gas_used, latest.gas_limit, latest.transactions.len());
+
+    let pending = &chain.vicinity;
+    println!("Pending block {} ({}):", pending.block_number, if chain.automine { "automine" } else { "mined on 'mine'" });
+    println!("  timestamp  {}", pending.block_timestamp);
+    println!("  coinbase   {:?}", pending.block_coinbase);
+    println!("  base fee   {} wei", pending.block_base_fee_per_gas);
+    if let Some(prevrandao) = pending.block_randomness {
+        println!("  prevrandao {:?}", prevrandao);
+    }
+    println!("  gas used   {} of {}, {} transaction(s)", chain.pending_gas_used, pending.block_gas_limit, chain.pending_transactions.len());
+}
+
+fn print_accounts(chain: &Chain, accounts: &[Account], sender: H160) {
+    println!("\n*** Accounts ***");
+    for (i, account) in accounts.iter().enumerate() {
+        let marker = if account.address == sender { "  (sender)" } else { "" };
+        println!("{}: {:?} {}{}", i, account.address, abi_format::format_ether(chain.balance(account.address)), marker);
+        if let Some(secret_key) = &account.secret_key {
+            println!("   private key: 0x{}", hex::encode(secret_key.serialize()));
+        }
+    }
+    if !accounts.iter().any(|account| account.address == sender) {
+        println!("sender: {:?} {}", sender, abi_format::format_ether(chain.balance(sender)));
+    }
+}
+
+fn run_repl(
+    chain: &mut Chain,
+    contracts_data: &mut ContractsData,
+    deployer: &Account,
+    accounts: &[Account],
+    state_path: Option<&str>,
+) -> Result<(), io::Error> {
+    print_accounts(chain, accounts, deployer.address);
+
+    // Deploy the contracts
+    println!("\n*** Start Deploying ***");
+    println!("deployer: {:?}", deployer.address);
+    println!("Type 'back' at a constructor prompt to skip that contract, or 'quit' to exit.");
+    if let Answer::Quit = deploy_contracts(chain, contracts_data, deployer)? {
+        return Ok(());
+    }
+
+    // Calls are sent by the deployer until the sender command picks another account
+    let mut sender = deployer.address;
+
+    // Interaction loop
+    println!("\nType 'back' at any prompt to return to the previous menu, or 'quit' to exit.");
+    loop {
+        // Ask the user which contract they want to interact with
+        let chosen_contract_name = match choose_contract(contracts_data)? {
+            Answer::Value(MenuChoice::Contract(name)) => name,
+            Answer::Value(MenuChoice::Command(words)) => {
+                run_command(&words, chain, contracts_data, deployer, accounts, &mut sender, state_path);
+                continue;
+            },
+            Answer::Back => continue,
+            Answer::Quit => break,
+        };
+
+        // Get the chosen contract data
+        let Some(contract_data) = contracts_data.get(&chosen_contract_name) else {
+            eprintln!("Contract {} not found", chosen_contract_name);
+            continue;
+        };
+
+        // Ask the user which function of the contract they want to call
+        println!("\nAvailable functions:");
+        let (chosen_function_name, is_getter, _return_types) = match choose_function(&contract_data.abi)? {
+            Answer::Value(chosen) => chosen,
+            Answer::Back => continue,
+            Answer::Quit => break,
+        };
+
+        // Get the function so we can iterate over it's inputs
+        let function = match contract_data.abi.function(&chosen_function_name) {
+            Ok(function) => function,
+            Err(e) => {
+                eprintln!("Function {} not found: {}", chosen_function_name, e);
+                continue;
+            }
+        };
+        let params = function.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
+
+        // Ask for function args
+        let args = match ask_for_function_inputs(&params, sender)? {
+            Answer::Value(args) => args,
+            Answer::Back => continue,
+            Answer::Quit => break,
+        };
+
+        // Encode function args
+        let encoded_args = match encode_function_args(&params, args) {
+            Ok(encoded_args) => encoded_args,
+            Err(e) => {
+                eprintln!("Invalid arguments: {}", e);
+                continue;
+            }
+        };
 
-            // Increment sender's nonce for the next contract
-            deployer.nonce += U256::one();
+        // Payable functions can be sent ether along with the call
+        let value = if function.state_mutability == ethabi::StateMutability::Payable {
+            match ask_for_value()? {
+                Answer::Value(value) => value,
+                Answer::Back => continue,
+                Answer::Quit => break,
+            }
         } else {
-            eprintln!("Failed to deploy contract {}: {:?}", contract_name, exit_reason);
-            return Err(io::Error::new(io::ErrorKind::Other, "Contract deployment failed"));
+            U256::zero()
+        };
+
+        // Call the function
+        match call_contract_function(chain, contracts_data, &chosen_contract_name, &chosen_function_name, encoded_args, sender, value) {
+            Ok(output) if is_getter => {
+                println!("Getter returned: {:?}", output);
+            },
+            Ok(output) => {
+                println!("Function call was successful. Output: {:?}", output);
+            },
+            Err(e) => {
+                // A failed call only rolls back that call, keep the session going
+                eprintln!("Function call failed: {}", e);
+            }
         }
     }
 
     Ok(())
 }
 
+fn tracing_mode(print: bool, json_path: &Option<String>) -> Tracing {
+    if print {
+        Tracing::Print
+    } else if json_path.is_some() {
+        Tracing::Record
+    } else {
+        Tracing::Off
+    }
+}
+
 // https://github.com/rust-blockchain/evm-tests
 fn main() -> Result<(), io::Error> {
 
-    let contracts_dir = "./contracts"; 
+    let cli = Cli::parse();
+    let options = &cli.options;
+
     let contract_names ; 
     let mut contracts_data: ContractsData = HashMap::new();
-    let mut deployer = Account {
-        address: H160::random(),
-        nonce: U256::zero(),
+
+    // Resume from a previous session if its state file exists
+    let snapshot = match &options.state {
+        Some(path) if Path::new(path).exists() => {
+            println!("Loading state from {}", path);
+            Some(state::load(path)?)
+        },
+        _ => None,
+    };
+
+    // Funded accounts with known keys, the same ones on every run for the same mnemonic
+    let accounts = (0..options.accounts)
+        .map(|index| {
+            let secret_key = keys::derive_private_key(&options.mnemonic, index)?;
+            Ok(Account { address: keys::address(&secret_key), secret_key: Some(secret_key) })
+        })
+        .collect::<Result<Vec<_>, io::Error>>()?;
+
+    // The first account deploys, unless a key is given or a saved session had another deployer
+    let deployer = match (&options.deployer_key, &snapshot) {
+        (Some(key), _) => {
+            let secret_key = keys::parse_private_key(key)?;
+            Account { address: keys::address(&secret_key), secret_key: Some(secret_key) }
+        },
+        (None, Some(snapshot)) => accounts.iter()
+            .find(|account| account.address == snapshot.deployer)
+            .cloned()
+            .unwrap_or(Account { address: snapshot.deployer, secret_key: None }),
+        (None, None) => accounts.first()
+            .cloned()
+            .unwrap_or_else(|| Account { address: H160::random(), secret_key: None }),
     };
+    let balance = abi_parse::parse_value(Some(&options.balance))?;
       
+    let gas_price = abi_parse::parse_value(Some(&options.gas_price))?;
+
+    // The first block starts at --timestamp or now, a saved session continues from its latest block
+    let timestamp = options.timestamp.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+    });
+    let (block_number, block_timestamp, base_fee) = match snapshot.as_ref().and_then(|snapshot| snapshot.block.as_ref()) {
+        Some(head) => (head.number, head.timestamp, head.base_fee),
+        None => (U256::zero(), U256::from(timestamp), abi_parse::parse_value(Some(&options.base_fee))?),
+    };
+
 	let vicinity = evm::backend::MemoryVicinity {
-		gas_price: U256::zero(),
+		gas_price: Default::default(),
 		origin: H160::default(),
 		block_hashes: Vec::new(),
-		block_number: Default::default(),
+		block_number,
 		block_coinbase: Default::default(),
-		block_timestamp: Default::default(),
+		block_timestamp,
 		block_difficulty: Default::default(),
-		block_gas_limit: Default::default(),
-		chain_id: U256::one(),
-		block_base_fee_per_gas: U256::zero(),
-		block_randomness: None,
+		block_gas_limit: U256::from(options.block_gas_limit),
+		chain_id: U256::from(options.chain_id),
+		block_base_fee_per_gas: base_fee,
+		// PREVRANDAO replaces DIFFICULTY from the merge on
+		block_randomness: (options.hardfork >= Hardfork::Merge).then(H256::zero),
 	};
 
-    // Runtime configuration.
-    let config = evm::Config {
-        gas_ext_code: 700,
-        gas_ext_code_hash: 700,
-        gas_balance: 700,
-        gas_sload: 800,
-        gas_sload_cold: 0,
-        gas_sstore_set: 20000,
-        gas_sstore_reset: 5000,
-        refund_sstore_clears: 15000,
-        max_refund_quotient: 2,
-        gas_suicide: 5000,
-        gas_suicide_new_account: 25000,
-        gas_call: 700,
-        gas_expbyte: 50,
-        gas_transaction_create: 53000,
-        gas_transaction_call: 21000,
-        gas_transaction_zero_data: 4,
-        gas_transaction_non_zero_data: 16,
-        gas_access_list_address: 0,
-        gas_access_list_storage_key: 0,
-        gas_account_access_cold: 0,
-        gas_storage_read_warm: 0,
-        sstore_gas_metering: true,
-        sstore_revert_under_stipend: true,
-        increase_state_access_gas: false,
-        decrease_clears_refund: false,
-        disallow_executable_format: false,
-        warm_coinbase_address: false,
-        err_on_call_with_more_gas: false,
-        empty_considered_exists: false,
-        create_increase_nonce: true,
-        call_l64_after_gas: true,
-        stack_limit: 1024,
-        memory_limit: usize::MAX,
-        call_stack_limit: 1024,
-        create_contract_limit: Some(0x6000),
-        max_initcode_size: None,
-        call_stipend: 2300,
-        has_delegate_call: true,
-        has_create2: true,
-        has_revert: true,
-        has_return_data: true,
-        has_bitwise_shifting: true,
-        has_chain_id: true,
-        has_self_balance: true,
-        has_ext_code_hash: true,
-        has_base_fee: false,
-        has_push0: true,
-        estimate: false,
+    // Runtime configuration: the rules of the chosen hardfork, with any overridden fields
+    let mut config = options.hardfork.config();
+    if let Some(path) = &options.evm_config {
+        hardfork::apply_overrides(&mut config, path)?;
+        println!("EVM config overrides loaded from {}", path);
+    }
+
+    // The hardfork's precompiles plus our native ones
+    let mut precompiles = Precompiles::new(options.hardfork);
+    natives::register_natives(&mut precompiles)?;
+
+    // Start from the accounts of a real chain when forking, the saved state goes on top
+    let mut genesis = match &options.fork_state {
+        Some(path) => {
+            let accounts = fork::load_state_dump(path)?;
+            println!("Loaded {} accounts from {}", accounts.len(), path);
+            accounts
+        },
+        None => BTreeMap::new(),
     };
+    if let Some(snapshot) = &snapshot {
+        genesis.extend(snapshot.genesis()?);
+    }
 
-    let precompiles = ();
+    // Make it rain for the deployer and the accounts, unless they already exist in the loaded state
+    for address in accounts.iter().chain([&deployer]).map(|account| account.address) {
+        genesis.entry(address).or_insert_with(|| MemoryAccount {
+            nonce: U256::zero(),
+            balance,
+            storage: BTreeMap::new(),
+            code: Vec::new(),
+        });
+    }
 
-    // Backends store state information of the VM, and exposes it to runtime.
-    // Initialize the backend with the vicinity and an empty state
-    let mut backend = MemoryBackend::new(&vicinity, Default::default());
-  
-    let metadata = StackSubstateMetadata::new(u64::MAX, &config);
+    // The backend stores state information of the VM, and exposes it to runtime.
+    // Every transaction runs on its own executor and commits its changes back to it.
+    let mut chain = Chain::new(vicinity, config, precompiles, genesis, options.gas_limit, gas_price);
+    chain.block_time = options.block_time;
+    chain.tracing = tracing_mode(options.trace, &options.trace_json);
+    chain.call_tracing = tracing_mode(options.call_trace, &options.call_trace_json);
 
-    // Now provide both the backend and the metadata to Memorystack_state::new
-    let mut stack_state = MemoryStackState::new(metadata, &mut backend);
-    
-    // Make it rain for the deployer
-    let account = stack_state.account_mut(deployer.address);
-    account.basic.balance = U256::max_value();
-    account.basic.nonce = U256::from(0);
-    
-    // EVM setup ready, make the executor       
-    let mut executor = StackExecutor::<'_, '_, _, _>::new_with_precompiles(stack_state, &config, &precompiles);
-    
     // Find the contracts in the contracts directory
-    match collect_contract_names(contracts_dir) {
+    match collect_contract_names(&options.contracts_dir) {
         Ok(names) => {
             contract_names = names; // Assign the names here
             println!("*** Contracts found ***");
@@ -579,9 +1311,13 @@ fn main() -> Result<(), io::Error> {
     }
 
     println!("\n*** Compiling contracts ***");
-    compile_contracts(contracts_dir, &contract_names).expect("Failed to compile contracts");
+    compile_contracts(&options.contracts_dir, &options.build_dir, &options.solc, &contract_names).expect("Failed to compile contracts");
+    if let Some(CliCommand::Compile) = cli.command {
+        println!("Compilation finished.");
+        return Ok(());
+    }
 
-    // Load ABIs and create ContractData entries
+    // Load ABIs and bytecode and create ContractData entries
     for contract_name in &contract_names {
         // Extract the contract base name without the .sol extension
         let contract_base_name = Path::new(contract_name)
@@ -590,61 +1326,152 @@ fn main() -> Result<(), io::Error> {
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid contract file name"))?
             .to_string();
     
-        let abi_path = format!("./build/contracts/{}/{}.abi", contract_base_name, contract_base_name);
+        let abi_path = format!("{}/{}/{}.abi", options.build_dir, contract_base_name, contract_base_name);
         let abi = parse_abi(&abi_path).expect("Failed to parse ABI");
+        let payable_constructor = has_payable_constructor(&abi_path)?;
+        let bytecode = load_bytecode(&options.build_dir, &contract_base_name)?;
     
         contracts_data.insert(contract_base_name.clone(), ContractData {
             address: None,  // Address to be filled in after deployment
             abi,
+            bytecode,
+            payable_constructor,
         });
     }
 
-    // Deploy the contracts
-    println!("\n*** Start Deploying ***");
-    println!("deployer: {:?}", deployer.address);
-    if let Err(e) = deploy_contracts(
-        &mut contracts_data,
-        &mut executor,
-        &mut deployer,
-    ) {
-        eprintln!("Error deploying contracts: {}", e);
-        exit(1);
+    // Native precompiles are called like contracts that are always deployed
+    for (address, native) in chain.precompiles.natives() {
+        if contracts_data.contains_key(&native.name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                format!("Contract {} has the name of a native precompile", native.name)));
+        }
+        contracts_data.insert(native.name.clone(), ContractData {
+            address: Some(*address),
+            abi: native.precompile.abi().clone(),
+            bytecode: Vec::new(),
+            payable_constructor: false,
+        });
     }
 
-    // Interaction loop
-    loop {
-        // Ask the user which contract they want to interact with
-        let chosen_contract_name = choose_contract(&contracts_data).expect("Failed to choose a contract");
-
-        // Get the chosen contract data
-        let contract_data = contracts_data.get(&chosen_contract_name).expect("Contract not found");
+    // Point the contracts at the addresses they were deployed to in the loaded state
+    if let Some(snapshot) = &snapshot {
+        for (name, address) in &snapshot.contracts {
+            if let Some(contract_data) = contracts_data.get_mut(name) {
+                contract_data.address = Some(*address);
+            }
+        }
+    }
 
-        // Ask the user which function of the contract they want to call
-        println!("\nAvailable functions:");
-        let (chosen_function_name, _is_getter, _return_types) = choose_function(&contract_data.abi).expect("Failed to choose a function");
+    let success = match &cli.command {
+        None | Some(CliCommand::Repl) => {
+            run_repl(&mut chain, &mut contracts_data, &deployer, &accounts, options.state.as_deref())?;
+            true
+        },
+        Some(CliCommand::Compile) => true,
+        Some(CliCommand::Serve { host, port }) => {
+            print_accounts(&chain, &accounts, deployer.address);
+            let mut node = rpc::Node {
+                chain: &mut chain,
+                contracts_data: &contracts_data,
+                accounts: accounts.iter().map(|account| account.address).collect(),
+                deployer: deployer.address,
+                state_path: options.state.as_deref(),
+            };
+            rpc::serve(&format!("{}:{}", host, port), &mut node)?;
+            true
+        },
+        Some(CliCommand::Run { scenario }) => {
+            // Headless mode: run a scenario file instead of the interactive session
+            println!("deployer: {:?}", deployer.address);
+            let passed = scenario::run_scenario(scenario, &mut chain, &mut contracts_data, deployer.address, &accounts)?;
+            chain.gas_report.print();
+            passed
+        },
+        Some(CliCommand::Deploy { contract, args, value, from }) => {
+            let sender = from.as_deref().map_or(Ok(deployer.address), |from| parse_sender(from, &accounts))?;
+            println!("deployer: {:?}", sender);
+            let encoded_args = encode_cli_args(&constructor_params(&contracts_data, contract)?, args)?;
+            let value = abi_parse::parse_value(value.as_deref())?;
+            match deploy_contract(&mut chain, &mut contracts_data, contract, encoded_args, sender, value) {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("Failed to deploy contract {}: {}", contract, e);
+                    false
+                }
+            }
+        },
+        Some(CliCommand::Send { to, value, data, from }) => {
+            let sender = from.as_deref().map_or(Ok(deployer.address), |from| parse_sender(from, &accounts))?;
+            let to = parse_recipient(to, &contracts_data, &accounts)?;
+            let value = abi_parse::parse_value(Some(value))?;
+            let data = parse_calldata(data.as_deref())?;
+            match send_transaction(&mut chain, &contracts_data, sender, to, value, data) {
+                Ok(output) => {
+                    println!("Transaction succeeded, returned 0x{}", hex::encode(output));
+                    true
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    false
+                }
+            }
+        },
+        Some(CliCommand::Call { contract, function, args, constructor_args, value, from }) => {
+            println!("deployer: {:?}", deployer.address);
+            if !ensure_deployed(&mut chain, &mut contracts_data, contract, constructor_args, deployer.address)? {
+                false
+            } else {
+                let params = contracts_data[contract].abi.function(function)
+                    .map_err(|_| io::Error::new(io::ErrorKind::NotFound, format!("Unknown function {}", function)))?
+                    .inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
+                let encoded_args = encode_cli_args(&params, args)?;
+                let value = abi_parse::parse_value(value.as_deref())?;
+                let sender = from.as_deref().map_or(Ok(deployer.address), |from| parse_sender(from, &accounts))?;
+                match call_contract_function(&mut chain, &contracts_data, contract, function, encoded_args, sender, value) {
+                    Ok(output) => {
+                        println!("Function call was successful. Output: [{}]",
+                            output.iter().map(abi_format::format_token).collect::<Vec<_>>().join(", "));
+                        true
+                    },
+                    Err(e) => {
+                        eprintln!("Function call failed: {}", e);
+                        false
+                    }
+                }
+            }
+        },
+    };
 
-        // Get the function so we can iterate over it's inputs
-        let function = contract_data.abi.function(&chosen_function_name).expect("Function not found");
-        
-        // Ask for constructor args
-        let args = ask_for_function_inputs(&function.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>(), deployer.address)?;
+    // Keep the world state for the next session
+    if let Some(path) = &options.state {
+        state::save(path, &state::Snapshot::take(&chain, deployer.address, &contracts_data))?;
+        println!("State saved to {}", path);
+    }
 
-        // Encode constructor args
-        let encoded_args = encode_function_args(&function.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>(), args)?;
+    // Dump the opcode trace of the last transaction
+    if let Some(path) = &options.trace_json {
+        match &chain.last_trace {
+            Some(trace) => {
+                tracer::write_struct_logs(path, trace)?;
+                println!("Trace of the last transaction written to {}", path);
+            },
+            None => eprintln!("No transaction was traced, {} not written", path),
+        }
+    }
 
-        // Call the function
-        match call_contract_function(&mut executor, contract_data, &chosen_function_name, encoded_args, deployer.address) {
-            Ok(output) => {
-                println!("Function call was successful. Output: {:?}", output);
+    if let Some(path) = &options.call_trace_json {
+        match chain.last_trace.as_ref().and_then(|trace| trace.call_tree.as_ref()) {
+            Some(root) => {
+                call_tree::write_call_tree(path, root, &contracts_data)?;
+                println!("Call tree of the last transaction written to {}", path);
             },
-            Err(e) => {
-                eprintln!("Function call failed: {}", e);
-                exit(1);
-            }
+            None => eprintln!("No transaction was traced, {} not written", path),
         }
     }
-    #[allow(unreachable_code)] 
-    Ok(())
-}
 
+    if !success {
+        exit(1);
+    }
 
+    Ok(())
+}
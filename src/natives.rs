@@ -0,0 +1,76 @@
+use ethabi::{Contract, Token};
+use ethereum_types::{H160, U256, U512};
+use evm::executor::stack::PrecompileFailure;
+use evm::ExitRevert;
+
+use std::io;
+
+use crate::precompiles::{Precompile, Precompiles};
+use crate::revert::encode_error;
+
+// Native functions of the Litheum chain, prototyped as precompiles before they land in
+// the node. A new one implements `Precompile` and is registered here at a free address.
+pub fn register_natives(precompiles: &mut Precompiles) -> Result<(), io::Error> {
+    precompiles.register("NativeMath", H160::from_low_u64_be(0x100), Box::new(NativeMath::new()?))
+}
+
+const NATIVE_MATH_ABI: &str = r#"[
+    {"type": "function", "name": "sqrt", "stateMutability": "pure",
+     "inputs": [{"name": "x", "type": "uint256"}],
+     "outputs": [{"name": "", "type": "uint256"}]},
+    {"type": "function", "name": "mulDiv", "stateMutability": "pure",
+     "inputs": [{"name": "a", "type": "uint256"}, {"name": "b", "type": "uint256"}, {"name": "denominator", "type": "uint256"}],
+     "outputs": [{"name": "", "type": "uint256"}]}
+]"#;
+
+// Integer square root and full precision a * b / denominator
+pub struct NativeMath {
+    abi: Contract,
+}
+
+impl NativeMath {
+    pub fn new() -> Result<Self, io::Error> {
+        let abi = Contract::load(NATIVE_MATH_ABI.as_bytes()).map_err(io::Error::other)?;
+        Ok(NativeMath { abi })
+    }
+}
+
+impl Precompile for NativeMath {
+    fn abi(&self) -> &Contract {
+        &self.abi
+    }
+
+    fn gas(&self, input: &[u8]) -> u64 {
+        match find_function(&self.abi, input).map(|function| function.name.as_str()) {
+            Some("sqrt") => 200,
+            Some("mulDiv") => 300,
+            _ => 0,
+        }
+    }
+
+    fn call(&self, input: &[u8], _context: &evm::Context) -> Result<Vec<u8>, PrecompileFailure> {
+        let function = find_function(&self.abi, input).ok_or_else(|| revert("unknown function"))?;
+        let args = function.decode_input(&input[4..]).map_err(|_| revert("invalid arguments"))?;
+        let args = args.into_iter().filter_map(Token::into_uint).collect::<Vec<_>>();
+
+        let result = match (function.name.as_str(), args.as_slice()) {
+            ("sqrt", [x]) => x.integer_sqrt(),
+            ("mulDiv", [_, _, denominator]) if denominator.is_zero() => return Err(revert("division by zero")),
+            ("mulDiv", [a, b, denominator]) => {
+                let result = a.full_mul(*b) / U512::from(*denominator);
+                U256::try_from(result).map_err(|_| revert("mulDiv overflows"))?
+            },
+            _ => return Err(revert("invalid arguments")),
+        };
+        Ok(ethabi::encode(&[Token::Uint(result)]))
+    }
+}
+
+fn find_function<'a>(abi: &'a Contract, input: &[u8]) -> Option<&'a ethabi::Function> {
+    let selector = input.get(..4)?;
+    abi.functions().find(|function| function.short_signature() == selector)
+}
+
+fn revert(message: &str) -> PrecompileFailure {
+    PrecompileFailure::Revert { exit_status: ExitRevert::Reverted, output: encode_error(message) }
+}
@@ -0,0 +1,574 @@
+use ethabi::Contract;
+use ethereum_types::{H160, U256};
+use evm::executor::stack::{IsPrecompileResult, PrecompileFailure, PrecompileHandle, PrecompileOutput, PrecompileSet};
+use evm::{ExitError, ExitSucceed};
+use bn::Group;
+use num_bigint::BigUint;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::hardfork::Hardfork;
+
+// The precompiled contracts of mainnet at 0x01 to 0x09
+#[derive(Clone, Copy, Debug)]
+pub enum Standard {
+    EcRecover,
+    Sha256,
+    Ripemd160,
+    Identity,
+    ModExp,
+    Bn128Add,
+    Bn128Mul,
+    Bn128Pairing,
+    Blake2F,
+}
+
+// A precompile written in Rust and registered at an address of our choosing, to
+// prototype chain-native features. Its ABI lets the REPL call it like a contract.
+pub trait Precompile {
+    fn abi(&self) -> &Contract;
+    // Charged before `call` runs
+    fn gas(&self, input: &[u8]) -> u64;
+    fn call(&self, input: &[u8], context: &evm::Context) -> Result<Vec<u8>, PrecompileFailure>;
+}
+
+pub struct Native {
+    pub name: String,
+    pub precompile: Box<dyn Precompile>,
+}
+
+// The precompiles of a hardfork, priced under its rules, and our own native ones
+pub struct Precompiles {
+    standard: BTreeMap<H160, Standard>,
+    natives: BTreeMap<H160, Native>,
+    // EIP-2565 modexp pricing, from Berlin on
+    cheaper_modexp: bool,
+    // EIP-1108 alt_bn128 pricing, from Istanbul on
+    cheaper_bn128: bool,
+}
+
+impl Precompiles {
+    pub fn new(hardfork: Hardfork) -> Self {
+        // Frontier only had the first four, Byzantium added 0x05-0x08 and Istanbul blake2f
+        let count = match hardfork {
+            Hardfork::Frontier => 4,
+            _ => 9,
+        };
+        let all = [
+            Standard::EcRecover,
+            Standard::Sha256,
+            Standard::Ripemd160,
+            Standard::Identity,
+            Standard::ModExp,
+            Standard::Bn128Add,
+            Standard::Bn128Mul,
+            Standard::Bn128Pairing,
+            Standard::Blake2F,
+        ];
+        let standard = all.into_iter()
+            .take(count)
+            .enumerate()
+            .map(|(i, precompile)| (H160::from_low_u64_be(i as u64 + 1), precompile))
+            .collect();
+
+        Precompiles {
+            standard,
+            natives: BTreeMap::new(),
+            cheaper_modexp: !matches!(hardfork, Hardfork::Frontier | Hardfork::Istanbul),
+            cheaper_bn128: !matches!(hardfork, Hardfork::Frontier),
+        }
+    }
+
+    pub fn register(&mut self, name: &str, address: H160, precompile: Box<dyn Precompile>) -> Result<(), io::Error> {
+        if let Some(precompile) = self.standard.get(&address) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                format!("Can't register {} at {:?}, {:?} is there", name, address, precompile)));
+        }
+        if let Some(native) = self.natives.get(&address) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                format!("Can't register {} at {:?}, {} is there", name, address, native.name)));
+        }
+        self.natives.insert(address, Native { name: name.to_string(), precompile });
+        Ok(())
+    }
+
+    pub fn contains(&self, address: H160) -> bool {
+        self.standard.contains_key(&address) || self.natives.contains_key(&address)
+    }
+
+    pub fn natives(&self) -> impl Iterator<Item = (&H160, &Native)> {
+        self.natives.iter()
+    }
+
+    // Charges the precompile's gas before doing any work, so oversized inputs fail cheaply
+    fn run(&self, precompile: Standard, handle: &mut impl PrecompileHandle) -> Result<Vec<u8>, PrecompileFailure> {
+        let input = handle.input().to_vec();
+        let words = (input.len() as u64).div_ceil(32);
+        let cost = match precompile {
+            Standard::EcRecover => 3000,
+            Standard::Sha256 => 60 + 12 * words,
+            Standard::Ripemd160 => 600 + 120 * words,
+            Standard::Identity => 15 + 3 * words,
+            Standard::ModExp => mod_exp_cost(&input, self.cheaper_modexp)?,
+            Standard::Bn128Add if self.cheaper_bn128 => 150,
+            Standard::Bn128Add => 500,
+            Standard::Bn128Mul if self.cheaper_bn128 => 6000,
+            Standard::Bn128Mul => 40000,
+            Standard::Bn128Pairing if self.cheaper_bn128 => 45000 + 34000 * (input.len() as u64 / 192),
+            Standard::Bn128Pairing => 100000 + 80000 * (input.len() as u64 / 192),
+            Standard::Blake2F => blake2_f_rounds(&input)? as u64,
+        };
+        handle.record_cost(cost)?;
+
+        match precompile {
+            Standard::EcRecover => Ok(ec_recover(&input)),
+            Standard::Sha256 => Ok(Sha256::digest(&input).to_vec()),
+            Standard::Ripemd160 => {
+                let mut output = vec![0u8; 12];
+                output.extend(ripemd::Ripemd160::digest(&input));
+                Ok(output)
+            },
+            Standard::Identity => Ok(input),
+            Standard::ModExp => Ok(mod_exp(&input)),
+            Standard::Bn128Add => bn128_add(&input),
+            Standard::Bn128Mul => bn128_mul(&input),
+            Standard::Bn128Pairing => bn128_pairing(&input),
+            Standard::Blake2F => blake2_f(&input),
+        }
+    }
+}
+
+impl PrecompileSet for Precompiles {
+    fn execute(&self, handle: &mut impl PrecompileHandle) -> Option<Result<PrecompileOutput, PrecompileFailure>> {
+        let address = handle.code_address();
+        let output = match (self.standard.get(&address), self.natives.get(&address)) {
+            (Some(precompile), _) => self.run(*precompile, handle),
+            (None, Some(native)) => {
+                let input = handle.input().to_vec();
+                handle.record_cost(native.precompile.gas(&input))
+                    .map_err(PrecompileFailure::from)
+                    .and_then(|()| native.precompile.call(&input, handle.context()))
+            },
+            (None, None) => return None,
+        };
+        Some(output.map(|output| PrecompileOutput { exit_status: ExitSucceed::Returned, output }))
+    }
+
+    fn is_precompile(&self, address: H160, _remaining_gas: u64) -> IsPrecompileResult {
+        IsPrecompileResult::Answer {
+            is_precompile: self.standard.contains_key(&address) || self.natives.contains_key(&address),
+            extra_cost: 0,
+        }
+    }
+}
+
+fn failure(message: &'static str) -> PrecompileFailure {
+    PrecompileFailure::Error { exit_status: ExitError::Other(Cow::Borrowed(message)) }
+}
+
+// Input shorter than expected is read as if padded with zeros
+fn padded<const N: usize>(input: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    if offset < input.len() {
+        let available = &input[offset..input.len().min(offset + N)];
+        bytes[..available.len()].copy_from_slice(available);
+    }
+    bytes
+}
+
+// Returns the signer's address, or nothing when the signature is invalid
+fn ec_recover(input: &[u8]) -> Vec<u8> {
+    let input = padded::<128>(input, 0);
+    let v = U256::from_big_endian(&input[32..64]);
+    if v != U256::from(27) && v != U256::from(28) {
+        return Vec::new();
+    }
+
+    let message = libsecp256k1::Message::parse_slice(&input[..32]);
+    let signature = libsecp256k1::Signature::parse_standard_slice(&input[64..128]);
+    let recovery_id = libsecp256k1::RecoveryId::parse(input[63] - 27);
+    let public_key = match (message, signature, recovery_id) {
+        (Ok(message), Ok(signature), Ok(recovery_id)) => libsecp256k1::recover(&message, &signature, &recovery_id),
+        _ => return Vec::new(),
+    };
+    match public_key {
+        Ok(public_key) => {
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&Keccak256::digest(&public_key.serialize()[1..])[12..]);
+            output
+        },
+        Err(_) => Vec::new(),
+    }
+}
+
+// The lengths of base, exponent and modulus, which head the input
+fn mod_exp_lengths(input: &[u8]) -> Result<(usize, usize, usize), ExitError> {
+    let base_len = U256::from_big_endian(&padded::<32>(input, 0));
+    let exp_len = U256::from_big_endian(&padded::<32>(input, 32));
+    let mod_len = U256::from_big_endian(&padded::<32>(input, 64));
+
+    // Lengths this large could never be paid for
+    let limit = U256::from(u32::MAX);
+    if base_len > limit || exp_len > limit || mod_len > limit {
+        return Err(ExitError::OutOfGas);
+    }
+    Ok((base_len.as_usize(), exp_len.as_usize(), mod_len.as_usize()))
+}
+
+// EIP-198 pricing, or EIP-2565 from Berlin on
+fn mod_exp_cost(input: &[u8], eip2565: bool) -> Result<u64, ExitError> {
+    let (base_len, exp_len, mod_len) = mod_exp_lengths(input)?;
+
+    // Only the first 32 bytes of the exponent count towards the price
+    let exp_head_bits = match exp_len {
+        0 => 0,
+        _ => (U256::from_big_endian(&padded::<32>(input, 96 + base_len)) >> (8 * (32 - exp_len.min(32)))).bits() as u64,
+    };
+    let adjusted_exp_len = if exp_len <= 32 {
+        exp_head_bits.saturating_sub(1)
+    } else {
+        8 * (exp_len as u64 - 32) + exp_head_bits.saturating_sub(1)
+    };
+
+    let max_len = base_len.max(mod_len) as u128;
+    let iterations = adjusted_exp_len.max(1) as u128;
+    let cost = if eip2565 {
+        let words = max_len.div_ceil(8);
+        (words * words * iterations / 3).max(200)
+    } else {
+        let complexity = if max_len <= 64 {
+            max_len * max_len
+        } else if max_len <= 1024 {
+            max_len * max_len / 4 + 96 * max_len - 3072
+        } else {
+            max_len * max_len / 16 + 480 * max_len - 199680
+        };
+        complexity * iterations / 20
+    };
+    u64::try_from(cost).map_err(|_| ExitError::OutOfGas)
+}
+
+fn mod_exp(input: &[u8]) -> Vec<u8> {
+    // The lengths were checked when pricing the call
+    let Ok((base_len, exp_len, mod_len)) = mod_exp_lengths(input) else {
+        return Vec::new();
+    };
+    if mod_len == 0 {
+        return Vec::new();
+    }
+    let read = |offset: usize, len: usize| {
+        let mut bytes = vec![0u8; len];
+        if offset < input.len() {
+            let available = &input[offset..input.len().min(offset + len)];
+            bytes[..available.len()].copy_from_slice(available);
+        }
+        BigUint::from_bytes_be(&bytes)
+    };
+    let exp_offset = 96 + base_len;
+    let base = read(96, base_len);
+    let exponent = read(exp_offset, exp_len);
+    let modulus = read(exp_offset + exp_len, mod_len);
+
+    let result = if modulus == BigUint::ZERO { BigUint::ZERO } else { base.modpow(&exponent, &modulus) };
+    let bytes = result.to_bytes_be();
+    let mut output = vec![0u8; mod_len];
+    output[mod_len - bytes.len()..].copy_from_slice(&bytes);
+    output
+}
+
+fn read_g1(input: &[u8], offset: usize) -> Result<bn::G1, PrecompileFailure> {
+    let x = bn::Fq::from_slice(&padded::<32>(input, offset)).map_err(|_| failure("invalid alt_bn128 field element"))?;
+    let y = bn::Fq::from_slice(&padded::<32>(input, offset + 32)).map_err(|_| failure("invalid alt_bn128 field element"))?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G1::zero());
+    }
+    bn::AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| failure("alt_bn128 point not on the curve"))
+}
+
+fn write_g1(point: bn::G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = bn::AffineG1::from_jacobian(point) {
+        // Both halves are exactly 32 bytes, so this can't fail
+        let _ = point.x().to_big_endian(&mut output[..32]);
+        let _ = point.y().to_big_endian(&mut output[32..]);
+    }
+    output
+}
+
+fn bn128_add(input: &[u8]) -> Result<Vec<u8>, PrecompileFailure> {
+    Ok(write_g1(read_g1(input, 0)? + read_g1(input, 64)?))
+}
+
+fn bn128_mul(input: &[u8]) -> Result<Vec<u8>, PrecompileFailure> {
+    let point = read_g1(input, 0)?;
+    let scalar = bn::Fr::from_slice(&padded::<32>(input, 64)).map_err(|_| failure("invalid alt_bn128 scalar"))?;
+    Ok(write_g1(point * scalar))
+}
+
+fn bn128_pairing(input: &[u8]) -> Result<Vec<u8>, PrecompileFailure> {
+    if !input.len().is_multiple_of(192) {
+        return Err(failure("alt_bn128 pairing input is not a multiple of 192 bytes"));
+    }
+    let mut pairs = Vec::new();
+    for offset in (0..input.len()).step_by(192) {
+        let g1 = read_g1(input, offset)?;
+        // Coordinates of G2 points are written imaginary part first
+        let fq = |at: usize| bn::Fq::from_slice(&input[offset + at..offset + at + 32]).map_err(|_| failure("invalid alt_bn128 field element"));
+        let x = bn::Fq2::new(fq(96)?, fq(64)?);
+        let y = bn::Fq2::new(fq(160)?, fq(128)?);
+        let g2 = if x.is_zero() && y.is_zero() {
+            bn::G2::zero()
+        } else {
+            bn::AffineG2::new(x, y).map(Into::into).map_err(|_| failure("alt_bn128 point not on the curve"))?
+        };
+        pairs.push((g1, g2));
+    }
+
+    let mut output = vec![0u8; 32];
+    if bn::pairing_batch(&pairs) == bn::Gt::one() {
+        output[31] = 1;
+    }
+    Ok(output)
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+// Blake2f costs one gas per round
+fn blake2_f_rounds(input: &[u8]) -> Result<u32, PrecompileFailure> {
+    if input.len() != 213 {
+        return Err(failure("blake2f input must be 213 bytes"));
+    }
+    Ok(u32::from_be_bytes(padded::<4>(input, 0)))
+}
+
+// The BLAKE2b compression function F with a configurable number of rounds (EIP-152)
+fn blake2_f(input: &[u8]) -> Result<Vec<u8>, PrecompileFailure> {
+    let rounds = blake2_f_rounds(input)?;
+    let final_block = match input[212] {
+        0 => false,
+        1 => true,
+        _ => return Err(failure("blake2f final block flag must be 0 or 1")),
+    };
+    let word = |at: usize| u64::from_le_bytes(padded::<8>(input, at));
+    let mut h: [u64; 8] = std::array::from_fn(|i| word(4 + 8 * i));
+    let m: [u64; 16] = std::array::from_fn(|i| word(68 + 8 * i));
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(&h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= word(196);
+    v[13] ^= word(204);
+    if final_block {
+        v[14] = !v[14];
+    }
+
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+
+    Ok(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        hex::decode(hex).unwrap()
+    }
+
+    // The lengths of base, exponent and modulus followed by the values, all given in hex
+    fn mod_exp_input(base: &str, exponent: &str, modulus: &str) -> Vec<u8> {
+        let mut input = Vec::new();
+        for part in [base, exponent, modulus] {
+            let mut length = [0u8; 32];
+            U256::from(part.len() / 2).to_big_endian(&mut length);
+            input.extend(length);
+        }
+        [input, bytes(base), bytes(exponent), bytes(modulus)].concat()
+    }
+
+    const SECP256K1_P: &str = "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f";
+    const SECP256K1_P_MINUS_1: &str = "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e";
+
+    #[test]
+    fn ec_recover_returns_the_signer() {
+        let input = bytes(concat!(
+            "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c",
+            "000000000000000000000000000000000000000000000000000000000000001c",
+            "73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f",
+            "eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549",
+        ));
+        assert_eq!(hex::encode(ec_recover(&input)), "000000000000000000000000a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+
+        // Any v but 27 and 28 recovers nothing
+        let mut bad_v = input.clone();
+        bad_v[63] = 29;
+        assert!(ec_recover(&bad_v).is_empty());
+    }
+
+    #[test]
+    fn mod_exp_matches_the_eip_198_examples() {
+        // Fermat's little theorem, 3 ^ (p - 1) mod p = 1
+        let fermat = mod_exp_input("03", SECP256K1_P_MINUS_1, SECP256K1_P);
+        assert_eq!(U256::from_big_endian(&mod_exp(&fermat)), U256::one());
+        // A zero length base reads as 0
+        let zero_base = mod_exp_input("", SECP256K1_P_MINUS_1, SECP256K1_P);
+        assert_eq!(mod_exp(&zero_base), vec![0u8; 32]);
+        // An empty modulus returns nothing
+        assert!(mod_exp(&mod_exp_input("03", "05", "")).is_empty());
+    }
+
+    #[test]
+    fn mod_exp_is_priced_by_eip_198_then_eip_2565() {
+        let fermat = mod_exp_input("03", SECP256K1_P_MINUS_1, SECP256K1_P);
+        assert_eq!(mod_exp_cost(&fermat, false).unwrap(), 13056);
+        assert_eq!(mod_exp_cost(&fermat, true).unwrap(), 1360);
+        // EIP-2565 charges at least 200
+        let small_exponent = mod_exp_input("03", "ffff", SECP256K1_P);
+        assert_eq!(mod_exp_cost(&small_exponent, false).unwrap(), 768);
+        assert_eq!(mod_exp_cost(&small_exponent, true).unwrap(), 200);
+    }
+
+    #[test]
+    fn bn128_add_and_mul() {
+        let add_input = bytes(concat!(
+            "18b18acfb4c2c30276db5411368e7185b311dd124691610c5d3b74034e093dc9",
+            "063c909c4720840cb5134cb9f59fa749755796819658d32efc0d288198f37266",
+            "07c2b7f58a84bd6145f00c9c2bc0bb1a187f20ff2c92963a88019e7c6a014eed",
+            "06614e20c147e940f2d70da3f74c9a17df361706a4485c742bd6788478fa17d7",
+        ));
+        assert_eq!(hex::encode(bn128_add(&add_input).unwrap()), concat!(
+            "2243525c5efd4b9c3d3c45ac0ca3fe4dd85e830a4ce6b65fa1eeaee202839703",
+            "301d1d33be6da8e509df21cc35964723180eed7532537db9ae5e7d48f195c915",
+        ));
+
+        // The generator times 2, then times a 64 bit scalar
+        let generator = concat!(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+        );
+        let double = bytes(&[generator, "0000000000000000000000000000000000000000000000000000000000000002"].concat());
+        assert_eq!(hex::encode(bn128_mul(&double).unwrap()), concat!(
+            "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+            "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+        ));
+        let mul_input = bytes(&[generator, "00000000000000000000000000000000000000000000000011138ce750fa15c2"].concat());
+        assert_eq!(hex::encode(bn128_mul(&mul_input).unwrap()), concat!(
+            "2ee78f3e0df914f70b0db093299e2ae045ef1d95d276b50ea976309c59a4078c",
+            "0546af50e91d8a137fbf83691c3ee5edb31f979e9c1e60857601926ea9363128",
+        ));
+
+        // (1, 3) is not on the curve
+        let mut off_curve = vec![0u8; 128];
+        off_curve[31] = 1;
+        off_curve[63] = 3;
+        assert!(bn128_add(&off_curve).is_err());
+    }
+
+    #[test]
+    fn bn128_pairing_checks_the_product_of_pairings() {
+        let g2 = concat!(
+            "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+            "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+            "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+            "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+        );
+        let g1 = concat!(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+        );
+        // -G1, with y = p - 2
+        let minus_g1 = concat!(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45",
+        );
+        let one = |output: Vec<u8>| U256::from_big_endian(&output) == U256::one();
+
+        // e(G1, G2) * e(-G1, G2) = 1
+        assert!(one(bn128_pairing(&bytes(&[g1, g2, minus_g1, g2].concat())).unwrap()));
+        assert!(!one(bn128_pairing(&bytes(&[g1, g2].concat())).unwrap()));
+        // No pairs is the empty product
+        assert!(one(bn128_pairing(&[]).unwrap()));
+        assert!(bn128_pairing(&[0u8; 191]).is_err());
+    }
+
+    // EIP-152 test vectors, compressing the padded block of "abc"
+    fn blake2_f_input(rounds: &str, final_block: &str) -> Vec<u8> {
+        bytes(&[
+            rounds,
+            "48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5",
+            "d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b",
+            "6162630000000000000000000000000000000000000000000000000000000000",
+            &"00".repeat(96),
+            "0300000000000000",
+            "0000000000000000",
+            final_block,
+        ].concat())
+    }
+
+    #[test]
+    fn blake2_f_matches_the_eip_152_vectors() {
+        let input = blake2_f_input("0000000c", "01");
+        assert_eq!(blake2_f_rounds(&input).unwrap(), 12);
+        assert_eq!(hex::encode(blake2_f(&input).unwrap()), concat!(
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1",
+            "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+        ));
+        assert_eq!(hex::encode(blake2_f(&blake2_f_input("0000000c", "00")).unwrap()), concat!(
+            "75ab69d3190a562c51aef8d88f1c2775876944407270c42c9844252c26d28752",
+            "98743e7f6d5ea2f2d3e8d226039cd31b4e426ac4f2d3d666a610c2116fde4735",
+        ));
+        assert_eq!(hex::encode(blake2_f(&blake2_f_input("00000000", "01")).unwrap()), concat!(
+            "08c9bcf367e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5",
+            "d282e6ad7f520e511f6c3e2b8c68059b9442be0454267ce079217e1319cde05b",
+        ));
+
+        assert!(blake2_f(&input[..212]).is_err());
+        assert!(blake2_f(&blake2_f_input("0000000c", "02")).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use dialoguer::Input;
+
+use std::io;
+
+// Words accepted at every prompt to navigate instead of answering
+const BACK_WORDS: &[&str] = &["back", "cancel"];
+const QUIT_WORDS: &[&str] = &["quit", "exit"];
+
+pub enum Answer<T> {
+    Value(T),
+    // Abandon the current step and return to the previous menu
+    Back,
+    // Leave the session
+    Quit,
+}
+
+pub fn ask(prompt: &str, allow_empty: bool) -> Result<Answer<String>, io::Error> {
+    let input: String = Input::new()
+        .with_prompt(prompt)
+        .allow_empty(allow_empty)
+        .interact_text()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    let word = input.trim();
+    if BACK_WORDS.contains(&word) {
+        Ok(Answer::Back)
+    } else if QUIT_WORDS.contains(&word) {
+        Ok(Answer::Quit)
+    } else {
+        Ok(Answer::Value(input))
+    }
+}
+
+// Asks for a 1-based menu entry until a valid one is given, returns it 0-based
+pub fn ask_index(prompt: &str, count: usize) -> Result<Answer<usize>, io::Error> {
+    loop {
+        match ask(prompt, false)? {
+            Answer::Value(input) => match input.trim().parse::<usize>() {
+                Ok(number) if (1..=count).contains(&number) => return Ok(Answer::Value(number - 1)),
+                _ => eprintln!("Please enter a number between 1 and {}, 'back' or 'quit'", count),
+            },
+            Answer::Back => return Ok(Answer::Back),
+            Answer::Quit => return Ok(Answer::Quit),
+        }
+    }
+}
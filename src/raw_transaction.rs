@@ -0,0 +1,422 @@
+use ethereum_types::{H160, H256, U256};
+use libsecp256k1::{Message, RecoveryId, Signature};
+use rlp::{Rlp, RlpStream};
+use sha3::{Digest, Keccak256};
+
+use std::io;
+
+use crate::access_list::AccessList;
+use crate::chain::{self, Chain, Transaction};
+use crate::history::Signed;
+use crate::keys;
+
+// A signed transaction as wallets send it to eth_sendRawTransaction: a legacy one,
+// optionally replay protected (EIP-155), or an EIP-2718 typed envelope of an EIP-2930
+// access list or EIP-1559 dynamic fee transaction
+pub struct RawTransaction {
+    // 0 legacy, 1 access list, 2 dynamic fee
+    pub transaction_type: u8,
+    // None for legacy transactions signed without replay protection
+    pub chain_id: Option<U256>,
+    pub nonce: U256,
+    // Legacy and access list transactions pay exactly `max_fee_per_gas`
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: U256,
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub access_list: AccessList,
+    // Signature, v being the y parity for typed transactions
+    pub v: U256,
+    pub r: U256,
+    pub s: U256,
+    // Recovered from the signature
+    pub from: H160,
+    // Hash of the raw bytes, as nodes identify transactions
+    pub hash: H256,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn rlp_error(e: rlp::DecoderError) -> io::Error {
+    invalid(format!("invalid transaction RLP: {}", e))
+}
+
+pub fn decode(raw: &[u8]) -> Result<RawTransaction, io::Error> {
+    let first = *raw.first().ok_or_else(|| invalid("empty transaction".to_string()))?;
+    // Typed transactions start with their type, RLP lists with 0xc0 or above
+    let (transaction_type, payload) = match first {
+        0xc0.. => (0, raw),
+        1 | 2 => (first, &raw[1..]),
+        _ => return Err(invalid(format!("unsupported transaction type {}", first))),
+    };
+    let rlp = Rlp::new(payload);
+    // The list header must account for every byte, anything after it is not part of the transaction
+    let info = rlp.payload_info().map_err(rlp_error)?;
+    if !rlp.is_list() || info.header_len + info.value_len != payload.len() {
+        return Err(invalid("invalid transaction RLP: not a single list".to_string()));
+    }
+    let fields = [9, 11, 12][transaction_type as usize];
+    let count = rlp.item_count().map_err(rlp_error)?;
+    if count != fields {
+        return Err(invalid(format!("invalid transaction RLP: {} fields instead of {}", count, fields)));
+    }
+
+    let mut transaction = match transaction_type {
+        0 => RawTransaction {
+            transaction_type,
+            chain_id: None,
+            nonce: rlp.val_at(0).map_err(rlp_error)?,
+            max_priority_fee_per_gas: rlp.val_at(1).map_err(rlp_error)?,
+            max_fee_per_gas: rlp.val_at(1).map_err(rlp_error)?,
+            gas_limit: rlp.val_at(2).map_err(rlp_error)?,
+            to: decode_to(&rlp.at(3).map_err(rlp_error)?)?,
+            value: rlp.val_at(4).map_err(rlp_error)?,
+            data: rlp.val_at(5).map_err(rlp_error)?,
+            access_list: Vec::new(),
+            v: U256::zero(),
+            r: U256::zero(),
+            s: U256::zero(),
+            from: H160::zero(),
+            hash: H256::zero(),
+        },
+        1 => RawTransaction {
+            transaction_type,
+            chain_id: Some(rlp.val_at(0).map_err(rlp_error)?),
+            nonce: rlp.val_at(1).map_err(rlp_error)?,
+            max_priority_fee_per_gas: rlp.val_at(2).map_err(rlp_error)?,
+            max_fee_per_gas: rlp.val_at(2).map_err(rlp_error)?,
+            gas_limit: rlp.val_at(3).map_err(rlp_error)?,
+            to: decode_to(&rlp.at(4).map_err(rlp_error)?)?,
+            value: rlp.val_at(5).map_err(rlp_error)?,
+            data: rlp.val_at(6).map_err(rlp_error)?,
+            access_list: decode_access_list(&rlp.at(7).map_err(rlp_error)?)?,
+            v: U256::zero(),
+            r: U256::zero(),
+            s: U256::zero(),
+            from: H160::zero(),
+            hash: H256::zero(),
+        },
+        _ => RawTransaction {
+            transaction_type,
+            chain_id: Some(rlp.val_at(0).map_err(rlp_error)?),
+            nonce: rlp.val_at(1).map_err(rlp_error)?,
+            max_priority_fee_per_gas: rlp.val_at(2).map_err(rlp_error)?,
+            max_fee_per_gas: rlp.val_at(3).map_err(rlp_error)?,
+            gas_limit: rlp.val_at(4).map_err(rlp_error)?,
+            to: decode_to(&rlp.at(5).map_err(rlp_error)?)?,
+            value: rlp.val_at(6).map_err(rlp_error)?,
+            data: rlp.val_at(7).map_err(rlp_error)?,
+            access_list: decode_access_list(&rlp.at(8).map_err(rlp_error)?)?,
+            v: U256::zero(),
+            r: U256::zero(),
+            s: U256::zero(),
+            from: H160::zero(),
+            hash: H256::zero(),
+        },
+    };
+
+    // The signature is always the last three fields
+    let v: U256 = rlp.val_at(fields - 3).map_err(rlp_error)?;
+    let r: U256 = rlp.val_at(fields - 2).map_err(rlp_error)?;
+    let s: U256 = rlp.val_at(fields - 1).map_err(rlp_error)?;
+    let recovery_id = if transaction_type == 0 {
+        // v is 27 or 28 before EIP-155, chain id * 2 + 35 or 36 after
+        if v == U256::from(27) || v == U256::from(28) {
+            v - 27
+        } else if v >= U256::from(35) {
+            transaction.chain_id = Some((v - 35) / 2);
+            (v - 35) % 2
+        } else {
+            return Err(invalid(format!("invalid signature v {}", v)));
+        }
+    } else if v <= U256::one() {
+        v
+    } else {
+        return Err(invalid(format!("invalid signature y parity {}", v)));
+    };
+
+    // The signature covers every field but itself, legacy EIP-155 transactions add
+    // the chain id and two zeros in its place
+    let mut stream = RlpStream::new();
+    if transaction_type != 0 {
+        stream.append_raw(&[transaction_type], 0);
+    }
+    let unsigned_fields = fields - 3;
+    let replay_protected = transaction_type == 0 && transaction.chain_id.is_some();
+    stream.begin_list(if replay_protected { unsigned_fields + 3 } else { unsigned_fields });
+    for i in 0..unsigned_fields {
+        stream.append_raw(rlp.at(i).map_err(rlp_error)?.as_raw(), 1);
+    }
+    if let (true, Some(chain_id)) = (replay_protected, transaction.chain_id) {
+        stream.append(&chain_id);
+        stream.append_empty_data();
+        stream.append_empty_data();
+    }
+    let signing_hash = Keccak256::digest(stream.out());
+
+    transaction.from = recover(&signing_hash, recovery_id, r, s)?;
+    (transaction.v, transaction.r, transaction.s) = (v, r, s);
+    transaction.hash = H256::from_slice(&Keccak256::digest(raw));
+    Ok(transaction)
+}
+
+// An empty string for creates, an address otherwise
+fn decode_to(rlp: &Rlp) -> Result<Option<H160>, io::Error> {
+    if rlp.is_empty() {
+        Ok(None)
+    } else {
+        rlp.as_val().map(Some).map_err(rlp_error)
+    }
+}
+
+// [[address, [storage key, ...]], ...]
+fn decode_access_list(rlp: &Rlp) -> Result<AccessList, io::Error> {
+    if !rlp.is_list() {
+        return Err(invalid("invalid transaction RLP: the access list is not a list".to_string()));
+    }
+    rlp.iter()
+        .map(|item| {
+            if item.item_count().map_err(rlp_error)? != 2 {
+                return Err(invalid("invalid transaction RLP: access list entries have 2 fields".to_string()));
+            }
+            Ok((item.val_at(0).map_err(rlp_error)?, item.list_at(1).map_err(rlp_error)?))
+        })
+        .collect()
+}
+
+fn recover(signing_hash: &[u8], recovery_id: U256, r: U256, s: U256) -> Result<H160, io::Error> {
+    let mut signature = [0u8; 64];
+    r.to_big_endian(&mut signature[..32]);
+    s.to_big_endian(&mut signature[32..]);
+    let signature = Signature::parse_standard(&signature)
+        .map_err(|e| invalid(format!("invalid signature: {:?}", e)))?;
+    // EIP-2: signatures with s in the upper half of the curve order are malleable copies
+    if signature.s.is_high() {
+        return Err(invalid("invalid signature: s is in the upper half of the curve order".to_string()));
+    }
+    let recovery_id = RecoveryId::parse(recovery_id.low_u32() as u8)
+        .map_err(|e| invalid(format!("invalid signature: {:?}", e)))?;
+    let message = Message::parse_slice(signing_hash)
+        .map_err(|e| invalid(format!("invalid signature: {:?}", e)))?;
+    let public_key = libsecp256k1::recover(&message, &signature, &recovery_id)
+        .map_err(|e| invalid(format!("could not recover the sender: {:?}", e)))?;
+    Ok(keys::public_key_address(&public_key))
+}
+
+impl RawTransaction {
+    // Checks what a node checks before accepting a transaction into its pool. The
+    // nonce, intrinsic gas and gas limits are checked by the chain when it runs.
+    pub fn validate(&self, chain: &Chain) -> Result<(), io::Error> {
+        match self.chain_id {
+            Some(chain_id) if chain_id != chain.vicinity.chain_id => {
+                return Err(invalid(format!("invalid chain id {}, expected {}", chain_id, chain.vicinity.chain_id)));
+            },
+            Some(_) => {},
+            None => println!("Warning: transaction is not replay protected (no EIP-155 chain id)"),
+        }
+        let unsupported = match self.transaction_type {
+            // EIP-2930 came with the EIP-2929 access costs in Berlin
+            1 if !chain.config.increase_state_access_gas => Some("access list (EIP-2930)"),
+            2 if !chain.config.has_base_fee => Some("dynamic fee (EIP-1559)"),
+            _ => None,
+        };
+        if let Some(kind) = unsupported {
+            return Err(invalid(format!("{} transactions are not supported by this hardfork", kind)));
+        }
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err(invalid(format!("max priority fee per gas {} is above the max fee per gas {}",
+                self.max_priority_fee_per_gas, self.max_fee_per_gas)));
+        }
+        let base_fee = chain.vicinity.block_base_fee_per_gas;
+        if self.max_fee_per_gas < base_fee {
+            return Err(invalid(format!("max fee per gas {} is below the base fee {}", self.max_fee_per_gas, base_fee)));
+        }
+        if self.gas_limit > U256::from(u64::MAX) {
+            return Err(invalid(format!("gas limit {} is too large", self.gas_limit)));
+        }
+        // Senders must be able to pay the most the transaction could cost, not only
+        // what it ends up costing
+        let cost = self.gas_limit.checked_mul(self.max_fee_per_gas).and_then(|fee| fee.checked_add(self.value));
+        let balance = chain.balance(self.from);
+        if cost.is_none_or(|cost| balance < cost) {
+            return Err(invalid(format!("insufficient funds for gas * max fee + value: {:?} has {} wei", self.from, balance)));
+        }
+        Ok(())
+    }
+
+    // The transaction the chain runs, paying the effective gas price at the given base fee
+    pub fn to_transaction(&self, base_fee: U256) -> Transaction {
+        let gas_price = self.max_fee_per_gas.min(base_fee.saturating_add(self.max_priority_fee_per_gas));
+        Transaction {
+            caller: self.from,
+            to: self.to,
+            value: self.value,
+            data: self.data.clone(),
+            gas_limit: Some(self.gas_limit.low_u64()),
+            gas_price: Some(gas_price),
+            access_list: self.access_list.clone(),
+            nonce: Some(self.nonce),
+            hash: Some(self.hash),
+        }
+    }
+
+    // What the receipt keeps of the envelope
+    pub fn signed(&self) -> Signed {
+        Signed {
+            transaction_type: self.transaction_type,
+            chain_id: self.chain_id,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas,
+            max_fee_per_gas: self.max_fee_per_gas,
+            access_list: self.access_list.clone(),
+            v: self.v,
+            r: self.r,
+            s: self.s,
+        }
+    }
+}
+
+// Decodes, validates and runs a signed transaction
+pub fn send(chain: &mut Chain, raw: &[u8]) -> Result<(RawTransaction, chain::Outcome), io::Error> {
+    let transaction = decode(raw)?;
+    transaction.validate(chain)?;
+    let outcome = chain.transact(transaction.to_transaction(chain.vicinity.block_base_fee_per_gas))?;
+    // The chain only records what it ran, the receipt it just added gets the envelope
+    if let Some(receipt) = chain.receipts.last_mut() {
+        receipt.signed = Some(transaction.signed());
+    }
+    Ok((transaction, outcome))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::tests::chain_with;
+
+    // Signed with the first anvil account, chain id 31337
+    const ANVIL_ACCOUNT: &str = "f39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+    const LEGACY: &str = "f86d80843b9aca008252089470997970c51812dc3a010c7d01b50e0d17dc79c8880de0b6b3a76400008082f4f5a014e6e75a2b6d3834cd6d41945f67dae2b4825ca00fcfbbed8126ee571b26b23aa00449fb166fcce5cbb317798942ddf7f539d21d4a76f9f0c1e11335201641d4d9";
+    const ACCESS_LIST: &str = "01f8c3827a6901843b9aca008275309470997970c51812dc3a010c7d01b50e0d17dc79c88080f85bf8599470997970c51812dc3a010c7d01b50e0d17dc79c8f842a00000000000000000000000000000000000000000000000000000000000000000a0000000000000000000000000000000000000000000000000000000000000000101a0c60ae119d9bca8b377c3a46e451618a9628830ab42e4909ab523693db0ae15dca02d6f8006a022e77fbd39a5336598c96be508410633cc448ba5c57a5293d94c29";
+    // A create
+    const DYNAMIC_FEE: &str = "02f86f827a6902843b9aca008477359400830186a0808096600a600c600039600a6000f3602a60005260206000f3c001a0d1700286f6f1664c9fac1540d0ce81e30d75026eb9f0e50bd03a822c1913126ea0500ab242809d8f6386402e21ae24240e7448a58137813087505cdb0c991518fb";
+    // The same key without a chain id
+    const UNPROTECTED: &str = "f86303843b9aca008252089470997970c51812dc3a010c7d01b50e0d17dc79c880801ca0cc95cd2ea47ad028113fb843ec747fc89276c06c48707edb3218be12c58b6544a00638d276a53a0d56cbd44a76a0c564b2cfa9c19b07d62496c6d888bec8efbfa8";
+    // The example of EIP-155, signed by the key 0x4646...46 for chain id 1
+    const EIP_155_EXAMPLE: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    fn decode_hex(raw: &str) -> RawTransaction {
+        match decode(&hex::decode(raw).unwrap()) {
+            Ok(transaction) => transaction,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn decode_error(raw: &[u8]) -> String {
+        match decode(raw) {
+            Ok(_) => panic!("the transaction decoded"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn address(hex: &str) -> H160 {
+        hex.parse().unwrap()
+    }
+
+    #[test]
+    fn decodes_a_replay_protected_legacy_transaction() {
+        let transaction = decode_hex(LEGACY);
+        assert_eq!(transaction.transaction_type, 0);
+        assert_eq!(transaction.chain_id, Some(U256::from(31337)));
+        assert_eq!(transaction.from, address(ANVIL_ACCOUNT));
+        assert_eq!(transaction.nonce, U256::zero());
+        assert_eq!(transaction.max_fee_per_gas, U256::from(1_000_000_000));
+        assert_eq!(transaction.to, Some(address("70997970c51812dc3a010c7d01b50e0d17dc79c8")));
+        assert_eq!(transaction.value, U256::exp10(18));
+        assert_eq!(transaction.hash, "0x9940d4e67823d4d99dfe256d70f3516c8b3588007140b22f182284f3ef0e6844".parse().unwrap());
+
+        let example = decode_hex(EIP_155_EXAMPLE);
+        assert_eq!(example.chain_id, Some(U256::one()));
+        assert_eq!(example.from, address("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"));
+    }
+
+    #[test]
+    fn decodes_an_unprotected_legacy_transaction() {
+        let transaction = decode_hex(UNPROTECTED);
+        assert_eq!(transaction.chain_id, None);
+        assert_eq!(transaction.v, U256::from(28));
+        assert_eq!(transaction.from, address(ANVIL_ACCOUNT));
+    }
+
+    #[test]
+    fn decodes_an_access_list_transaction() {
+        let transaction = decode_hex(ACCESS_LIST);
+        assert_eq!(transaction.transaction_type, 1);
+        assert_eq!(transaction.chain_id, Some(U256::from(31337)));
+        assert_eq!(transaction.from, address(ANVIL_ACCOUNT));
+        assert_eq!(transaction.nonce, U256::one());
+        let recipient = address("70997970c51812dc3a010c7d01b50e0d17dc79c8");
+        assert_eq!(transaction.access_list, vec![(recipient, vec![H256::zero(), H256::from_low_u64_be(1)])]);
+    }
+
+    #[test]
+    fn decodes_a_dynamic_fee_transaction() {
+        let transaction = decode_hex(DYNAMIC_FEE);
+        assert_eq!(transaction.transaction_type, 2);
+        assert_eq!(transaction.from, address(ANVIL_ACCOUNT));
+        assert_eq!(transaction.to, None);
+        assert_eq!(transaction.max_priority_fee_per_gas, U256::from(1_000_000_000));
+        assert_eq!(transaction.max_fee_per_gas, U256::from(2_000_000_000));
+        assert_eq!(transaction.gas_limit, U256::from(100_000));
+        assert_eq!(transaction.hash, "0xca7450ec3b10ca0901922722f31f66363e1521621a02bbb9b797a860dd8955fe".parse().unwrap());
+        // Pays the base fee plus the priority fee, up to the max fee
+        assert_eq!(transaction.to_transaction(U256::from(500_000_000)).gas_price, Some(U256::from(1_500_000_000)));
+        assert_eq!(transaction.to_transaction(U256::from(1_500_000_000)).gas_price, Some(U256::from(2_000_000_000)));
+    }
+
+    #[test]
+    fn rejects_another_chain_id() {
+        let transaction = decode_hex(EIP_155_EXAMPLE);
+        let chain = chain_with(transaction.from, U256::exp10(20));
+        let Err(e) = transaction.validate(&chain) else {
+            panic!("a transaction for chain 1 was accepted on chain 31337");
+        };
+        assert_eq!(e.to_string(), "invalid chain id 1, expected 31337");
+
+        let transaction = decode_hex(LEGACY);
+        let chain = chain_with(transaction.from, U256::exp10(20));
+        assert!(transaction.validate(&chain).is_ok());
+    }
+
+    #[test]
+    fn rejects_high_s_signatures() {
+        // n - s is as valid a signature, but EIP-2 only allows the lower one
+        let raw = hex::decode(LEGACY).unwrap();
+        let rlp = Rlp::new(&raw);
+        let order = U256::from_str_radix("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", 16).unwrap();
+        let s: U256 = rlp.val_at(8).unwrap();
+        let mut stream = RlpStream::new_list(9);
+        for i in 0..8 {
+            stream.append_raw(rlp.at(i).unwrap().as_raw(), 1);
+        }
+        stream.append(&(order - s));
+        assert_eq!(decode_error(&stream.out()), "invalid signature: s is in the upper half of the curve order");
+    }
+
+    #[test]
+    fn rejects_malformed_envelopes() {
+        let mut trailing = hex::decode(DYNAMIC_FEE).unwrap();
+        trailing.push(0);
+        assert_eq!(decode_error(&trailing), "invalid transaction RLP: not a single list");
+        let mut truncated = hex::decode(LEGACY).unwrap();
+        truncated.pop();
+        assert!(decode_error(&truncated).starts_with("invalid transaction RLP"));
+
+        let legacy = hex::decode(LEGACY).unwrap();
+        assert_eq!(decode_error(&[&[1], &legacy[..]].concat()), "invalid transaction RLP: 9 fields instead of 11");
+        assert_eq!(decode_error(&[3, 0xc0]), "unsupported transaction type 3");
+        assert_eq!(decode_error(&[]), "empty transaction");
+    }
+}
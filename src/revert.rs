@@ -0,0 +1,108 @@
+use ethabi::param_type::ParamType;
+use ethereum_types::U256;
+
+use std::fmt;
+use std::io;
+
+use crate::abi_format::format_token;
+use crate::ContractsData;
+
+// Selector of Error(string), used by require(cond, "message") and revert("message")
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+// Selector of Panic(uint256), used by assert, checked arithmetic and bounds checks
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+// Error of a call that ran and then reverted or failed, as opposed to one that was
+// rejected before running, like for a bad nonce or a gas limit above the block's
+#[derive(Debug)]
+pub struct Reverted(pub String);
+
+impl fmt::Display for Reverted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Call {}", self.0)
+    }
+}
+
+impl std::error::Error for Reverted {}
+
+pub fn is_revert(error: &io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<Reverted>())
+}
+
+// Payload of revert("message"), for reverting from native code
+pub fn encode_error(message: &str) -> Vec<u8> {
+    [ERROR_SELECTOR.to_vec(), ethabi::encode(&[ethabi::Token::String(message.to_string())])].concat()
+}
+
+// Describes the payload returned by a reverted call or deployment. Custom errors
+// are looked up in the errors section of every known contract ABI.
+pub fn decode_revert(output: &[u8], contracts: &ContractsData) -> String {
+    if output.is_empty() {
+        return "reverted without a reason".to_string();
+    }
+    if output.len() < 4 {
+        return format!("reverted with malformed data 0x{}", hex::encode(output));
+    }
+
+    let (selector, data) = output.split_at(4);
+
+    if selector == ERROR_SELECTOR {
+        if let Ok(tokens) = ethabi::decode(&[ParamType::String], data) {
+            if let Some(ethabi::Token::String(reason)) = tokens.first() {
+                return format!("reverted with reason {:?}", reason);
+            }
+        }
+    }
+
+    if selector == PANIC_SELECTOR {
+        if let Ok(tokens) = ethabi::decode(&[ParamType::Uint(256)], data) {
+            if let Some(ethabi::Token::Uint(code)) = tokens.first() {
+                return format!("panicked with code 0x{:02x}: {}", code, describe_panic(*code));
+            }
+        }
+    }
+
+    let custom_error = contracts.iter().find_map(|(contract_name, contract_data)| {
+        contract_data.abi.errors()
+            .find(|error| error.signature()[..4] == *selector)
+            .map(|error| (contract_name, error))
+    });
+    if let Some((contract_name, error)) = custom_error {
+        if let Ok(tokens) = error.decode(data) {
+            let args = error.inputs.iter().zip(tokens.iter())
+                .map(|(input, token)| {
+                    if input.name.is_empty() {
+                        format_token(token)
+                    } else {
+                        format!("{}: {}", input.name, format_token(token))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            return format!("reverted with custom error {}.{}({})", contract_name, error.name, args);
+        }
+    }
+
+    format!("reverted with unknown data 0x{}", hex::encode(output))
+}
+
+// Panic codes emitted by the Solidity compiler, see
+// https://docs.soliditylang.org/en/latest/control-structures.html#panic-via-assert-and-error-via-require
+fn describe_panic(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic code";
+    }
+    match code.low_u32() {
+        0x00 => "generic compiler inserted panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "conversion to an invalid enum value",
+        0x22 => "incorrectly encoded storage byte array",
+        0x31 => "pop() on an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory, too much memory allocated or array too large",
+        0x51 => "call to a zero-initialized internal function",
+        _ => "unknown panic code",
+    }
+}
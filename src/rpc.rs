@@ -0,0 +1,710 @@
+use ethereum_types::{Bloom, H160, H256, U256};
+use evm::ExitReason;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use std::io::{self, Read};
+
+use crate::block::Block;
+use crate::chain::{Chain, Outcome, Transaction};
+use crate::history::Receipt;
+use crate::{events, raw_transaction, revert, state, ContractsData};
+
+// keccak256(rlp([])), the uncles hash of every block without uncles
+const EMPTY_UNCLES_HASH: &str = "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347";
+
+// Everything the server works on besides the requests
+pub struct Node<'a> {
+    pub chain: &'a mut Chain,
+    pub contracts_data: &'a ContractsData,
+    // Returned by eth_accounts
+    pub accounts: Vec<H160>,
+    pub deployer: H160,
+    // The world state is written here after every transaction, when set
+    pub state_path: Option<&'a str>,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError { code: -32602, message: message.into(), data: None }
+    }
+
+    fn server(message: impl Into<String>) -> Self {
+        RpcError { code: -32000, message: message.into(), data: None }
+    }
+}
+
+impl From<io::Error> for RpcError {
+    fn from(e: io::Error) -> Self {
+        RpcError::server(e.to_string())
+    }
+}
+
+// Serves JSON-RPC over HTTP until the process is stopped. Requests are handled one
+// at a time on the chain the other subcommands use.
+pub fn serve(address: &str, node: &mut Node) -> Result<(), io::Error> {
+    let server = Server::http(address).map_err(|e| io::Error::other(format!("Cannot listen on {}: {}", address, e)))?;
+    println!("\n*** Listening on http://{} ***", address);
+
+    for mut request in server.incoming_requests() {
+        // Browsers ask before posting from another origin
+        if *request.method() == Method::Options {
+            let response = with_cors(Response::from_string(""));
+            if let Err(e) = request.respond(response) {
+                eprintln!("Failed to respond: {}", e);
+            }
+            continue;
+        }
+
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => handle_body(node, &body),
+            Err(e) => error_response(Value::Null, RpcError { code: -32700, message: e.to_string(), data: None }),
+        };
+        let response = with_cors(Response::from_string(response.to_string()))
+            .with_header(header("Content-Type", "application/json"));
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to respond: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static headers are valid")
+}
+
+fn with_cors<R: Read>(response: Response<R>) -> Response<R> {
+    response
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .with_header(header("Access-Control-Allow-Methods", "POST, OPTIONS"))
+        .with_header(header("Access-Control-Allow-Headers", "Content-Type"))
+}
+
+// A body holds a single request or a batch of them
+fn handle_body(node: &mut Node, body: &str) -> Value {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Array(requests)) if !requests.is_empty() => {
+            Value::Array(requests.iter().map(|request| handle_request(node, request)).collect())
+        },
+        Ok(request) => handle_request(node, &request),
+        Err(e) => error_response(Value::Null, RpcError { code: -32700, message: format!("Parse error: {}", e), data: None }),
+    }
+}
+
+fn handle_request(node: &mut Node, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, RpcError { code: -32600, message: "Invalid request: no method".to_string(), data: None });
+    };
+    let params = match request.get("params") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(params)) => params.clone(),
+        Some(_) => return error_response(id, RpcError::invalid_params("params must be an array")),
+    };
+
+    println!("{}", method);
+    match dispatch(node, method, &params) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => {
+            println!("  error: {}", error.message);
+            error_response(id, error)
+        },
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+fn dispatch(node: &mut Node, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+    let chain = &mut *node.chain;
+    match method {
+        "web3_clientVersion" => Ok(json!(format!("lithevm/{}", env!("CARGO_PKG_VERSION")))),
+        "net_version" => Ok(json!(chain.vicinity.chain_id.to_string())),
+        "net_listening" => Ok(json!(true)),
+        "eth_chainId" => Ok(quantity(chain.vicinity.chain_id)),
+        "eth_syncing" => Ok(json!(false)),
+        "eth_accounts" => Ok(json!(node.accounts)),
+        "eth_blockNumber" => Ok(quantity(chain.latest_block().number)),
+        "eth_gasPrice" => Ok(quantity(chain.gas_price.max(chain.vicinity.block_base_fee_per_gas))),
+        "eth_maxPriorityFeePerGas" => Ok(quantity(chain.gas_price.saturating_sub(chain.vicinity.block_base_fee_per_gas))),
+        "eth_feeHistory" => fee_history(chain, params),
+        "eth_getBalance" => {
+            let address = parse_address(required(params, 0)?)?;
+            check_latest_state(chain, params.get(1))?;
+            Ok(quantity(chain.balance(address)))
+        },
+        "eth_getTransactionCount" => {
+            let address = parse_address(required(params, 0)?)?;
+            check_latest_state(chain, params.get(1))?;
+            Ok(quantity(chain.nonce(address)))
+        },
+        "eth_getCode" => {
+            let address = parse_address(required(params, 0)?)?;
+            check_latest_state(chain, params.get(1))?;
+            let code = chain.state.get(&address).map(|account| account.code.as_slice()).unwrap_or_default();
+            Ok(data(code))
+        },
+        "eth_getStorageAt" => {
+            let address = parse_address(required(params, 0)?)?;
+            let mut slot = [0u8; 32];
+            parse_quantity(required(params, 1)?)?.to_big_endian(&mut slot);
+            check_latest_state(chain, params.get(2))?;
+            let value = chain.state.get(&address)
+                .and_then(|account| account.storage.get(&H256(slot)))
+                .copied()
+                .unwrap_or_default();
+            Ok(json!(value))
+        },
+        "eth_call" => {
+            let transaction = parse_transaction(chain, required(params, 0)?, H160::zero())?;
+            check_latest_state(chain, params.get(1))?;
+            let outcome = chain.simulate(transaction)?;
+            check_outcome(&outcome, node.contracts_data)?;
+            Ok(data(&outcome.output))
+        },
+        "eth_estimateGas" => {
+            let transaction = parse_transaction(chain, required(params, 0)?, H160::zero())?;
+            check_latest_state(chain, params.get(1))?;
+            estimate_gas(chain, transaction, node.contracts_data).map(quantity)
+        },
+        "eth_createAccessList" => {
+            let transaction = parse_transaction(chain, required(params, 0)?, H160::zero())?;
+            check_latest_state(chain, params.get(1))?;
+            let (access_list, outcome) = chain.create_access_list(transaction)?;
+            let mut json = json!({
+                "accessList": access_list_json(&access_list),
+                "gasUsed": quantity(outcome.gas_used),
+            });
+            // Like geth, a failing call still gets its list, with the failure next to it
+            if let Err(error) = check_outcome(&outcome, node.contracts_data) {
+                json["error"] = json!(error.message);
+            }
+            Ok(json)
+        },
+        "eth_sendTransaction" => {
+            let transaction = parse_transaction(chain, required(params, 0)?, node.deployer)?;
+            // Like a node only signing for the keys it holds
+            if transaction.caller != node.deployer && !node.accounts.contains(&transaction.caller) {
+                return Err(RpcError::server("unknown account"));
+            }
+            let outcome = chain.transact(transaction)?;
+            report_transaction(node, &outcome);
+            Ok(json!(outcome.transaction_hash))
+        },
+        "eth_sendRawTransaction" => {
+            let raw = parse_hex(required(params, 0)?)?;
+            let (transaction, outcome) = raw_transaction::send(chain, &raw)?;
+            println!("  Signed by {:?}, nonce {}", transaction.from, transaction.nonce);
+            report_transaction(node, &outcome);
+            Ok(json!(transaction.hash))
+        },
+        "eth_getTransactionByHash" => {
+            let hash = parse_hash(required(params, 0)?)?;
+            Ok(chain.receipts.iter()
+                .find(|receipt| receipt.transaction_hash == hash)
+                .map_or(Value::Null, |receipt| transaction_json(chain, receipt)))
+        },
+        "eth_getTransactionReceipt" => {
+            let hash = parse_hash(required(params, 0)?)?;
+            // Transactions still in the pending block have no receipt yet, like on a node
+            Ok(chain.receipts.iter()
+                .position(|receipt| receipt.transaction_hash == hash && receipt.block_hash.is_some())
+                .map_or(Value::Null, |position| receipt_json(&chain.receipts, position)))
+        },
+        "eth_getLogs" => get_logs(chain, required(params, 0)?),
+        "eth_getBlockByNumber" => {
+            let number = parse_block_number(chain, required(params, 0)?)?;
+            let full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+            Ok(find_block(chain, number).map_or(Value::Null, |block| block_json(chain, block, full)))
+        },
+        "eth_getBlockByHash" => {
+            let hash = parse_hash(required(params, 0)?)?;
+            let full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+            Ok(chain.blocks.iter()
+                .find(|block| block.hash == hash)
+                .map_or(Value::Null, |block| block_json(chain, block, full)))
+        },
+        _ => Err(RpcError { code: -32601, message: format!("Method {} is not supported", method), data: None }),
+    }
+}
+
+// Prints a transaction sent over RPC like the other subcommands do, and saves the state
+fn report_transaction(node: &mut Node, outcome: &Outcome) {
+    outcome.print_inclusion();
+    outcome.print_gas();
+    events::print_logs(&outcome.logs, node.contracts_data);
+    match outcome.exit_reason {
+        ExitReason::Succeed(_) => {},
+        ExitReason::Revert(_) => println!("  Transaction {}", revert::decode_revert(&outcome.output, node.contracts_data)),
+        _ => println!("  Transaction {}", outcome.describe_failure()),
+    }
+
+    if let Some(path) = node.state_path {
+        let snapshot = state::Snapshot::take(node.chain, node.deployer, node.contracts_data);
+        if let Err(e) = state::save(path, &snapshot) {
+            eprintln!("Failed to save state to {}: {}", path, e);
+        }
+    }
+}
+
+// Reverts are reported with code 3 and the revert data, the way clients expect to decode them
+fn check_outcome(outcome: &Outcome, contracts_data: &ContractsData) -> Result<(), RpcError> {
+    match outcome.exit_reason {
+        ExitReason::Succeed(_) => Ok(()),
+        ExitReason::Revert(_) => Err(RpcError {
+            code: 3,
+            message: format!("execution {}", revert::decode_revert(&outcome.output, contracts_data)),
+            data: Some(data(&outcome.output)),
+        }),
+        _ => Err(RpcError::server(outcome.describe_failure())),
+    }
+}
+
+// Finds the lowest gas limit the transaction succeeds with
+fn estimate_gas(chain: &mut Chain, transaction: Transaction, contracts_data: &ContractsData) -> Result<U256, RpcError> {
+    let block_gas_limit = chain.vicinity.block_gas_limit.min(U256::from(u64::MAX)).low_u64();
+    let cap = transaction.gas_limit.unwrap_or(block_gas_limit);
+    let mut run = |gas_limit: u64| chain.simulate(Transaction { gas_limit: Some(gas_limit), ..transaction.clone() });
+
+    let outcome = run(cap)?;
+    check_outcome(&outcome, contracts_data)?;
+
+    // The gas used before the refund is a lower bound. Calls that forward gas need
+    // more than that because of the 63/64 rule, so try a bit above it first.
+    let mut low = (outcome.gas_used + outcome.gas_refund).saturating_sub(1);
+    let mut high = cap;
+    let optimistic = (low + 1).saturating_mul(64) / 63;
+    if optimistic < high {
+        if run(optimistic)?.exit_reason.is_succeed() {
+            high = optimistic;
+        } else {
+            low = optimistic;
+        }
+    }
+    while low + 1 < high {
+        let middle = low + (high - low) / 2;
+        if run(middle)?.exit_reason.is_succeed() {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    Ok(U256::from(high))
+}
+
+fn fee_history(chain: &Chain, params: &[Value]) -> Result<Value, RpcError> {
+    let count = parse_quantity(required(params, 0)?)?.clamp(U256::one(), U256::from(1024)).low_u64();
+    let newest = parse_block_number(chain, required(params, 1)?)?.min(chain.latest_block().number);
+    let percentiles = params.get(2).and_then(Value::as_array).map_or(0, Vec::len);
+
+    let oldest = chain.blocks.first().map_or(U256::zero(), |block| block.number)
+        .max(newest.saturating_sub(U256::from(count - 1)));
+    let blocks = chain.blocks.iter()
+        .filter(|block| block.number >= oldest && block.number <= newest)
+        .collect::<Vec<_>>();
+
+    // The base fee after the newest block is included too
+    let mut base_fees = blocks.iter().map(|block| quantity(block.base_fee)).collect::<Vec<_>>();
+    let next_base_fee = find_block(chain, newest + 1).map_or(chain.vicinity.block_base_fee_per_gas, |block| block.base_fee);
+    base_fees.push(quantity(next_base_fee));
+    let ratios = blocks.iter()
+        .map(|block| if block.gas_limit.is_zero() { 0.0 } else { block.gas_used as f64 / block.gas_limit.low_u64() as f64 })
+        .collect::<Vec<_>>();
+    let mut history = json!({ "oldestBlock": quantity(oldest), "baseFeePerGas": base_fees, "gasUsedRatio": ratios });
+    if percentiles > 0 {
+        let tip = chain.gas_price.saturating_sub(chain.vicinity.block_base_fee_per_gas);
+        history["reward"] = json!(blocks.iter().map(|_| vec![quantity(tip); percentiles]).collect::<Vec<_>>());
+    }
+    Ok(history)
+}
+
+fn get_logs(chain: &Chain, filter: &Value) -> Result<Value, RpcError> {
+    let latest = chain.latest_block().number;
+    let (from, to) = match filter.get("blockHash").filter(|hash| !hash.is_null()) {
+        Some(hash) => {
+            let hash = parse_hash(hash)?;
+            let block = chain.blocks.iter().find(|block| block.hash == hash)
+                .ok_or_else(|| RpcError::server(format!("Unknown block {:?}", hash)))?;
+            (block.number, block.number)
+        },
+        None => {
+            let block_param = |name: &str| match filter.get(name).filter(|value| !value.is_null()) {
+                Some(value) => parse_block_number(chain, value),
+                None => Ok(latest),
+            };
+            (block_param("fromBlock")?, block_param("toBlock")?)
+        },
+    };
+
+    let addresses = match filter.get("address") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(addresses)) => addresses.iter().map(parse_address).collect::<Result<Vec<_>, _>>()?,
+        Some(address) => vec![parse_address(address)?],
+    };
+    // Each position holds null for any topic, or the topics one of which must match
+    let topics = match filter.get("topics") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(positions)) => positions.iter()
+            .map(|position| match position {
+                Value::Null => Ok(Vec::new()),
+                Value::Array(options) => options.iter().map(parse_hash).collect(),
+                topic => Ok(vec![parse_hash(topic)?]),
+            })
+            .collect::<Result<Vec<_>, RpcError>>()?,
+        Some(_) => return Err(RpcError::invalid_params("topics must be an array")),
+    };
+
+    let mut logs = Vec::new();
+    for (position, receipt) in chain.receipts.iter().enumerate() {
+        if receipt.block_hash.is_none() || receipt.block_number < from || receipt.block_number > to {
+            continue;
+        }
+        let first_log_index = first_log_index(&chain.receipts, position);
+        for (i, log) in receipt.logs.iter().enumerate() {
+            let address_matches = addresses.is_empty() || addresses.contains(&log.address);
+            let topics_match = topics.iter().enumerate().all(|(j, options)| {
+                options.is_empty() || log.topics.get(j).is_some_and(|topic| options.contains(topic))
+            });
+            if address_matches && topics_match {
+                logs.push(log_json(receipt, i, first_log_index + i));
+            }
+        }
+    }
+    Ok(Value::Array(logs))
+}
+
+// Log indexes count from the first log of the block
+fn first_log_index(receipts: &[Receipt], position: usize) -> usize {
+    let block_number = receipts[position].block_number;
+    receipts[..position].iter().rev()
+        .take_while(|receipt| receipt.block_number == block_number)
+        .map(|receipt| receipt.logs.len())
+        .sum()
+}
+
+// `i` is the position of the log in the receipt, `log_index` the one in the block
+fn log_json(receipt: &Receipt, i: usize, log_index: usize) -> Value {
+    let log = &receipt.logs[i];
+    json!({
+        "address": log.address,
+        "topics": log.topics,
+        "data": data(&log.data),
+        "blockNumber": quantity(receipt.block_number),
+        "blockHash": receipt.block_hash,
+        "transactionHash": receipt.transaction_hash,
+        "transactionIndex": quantity(receipt.transaction_index),
+        "logIndex": quantity(log_index),
+        "removed": false,
+    })
+}
+
+fn receipt_json(receipts: &[Receipt], position: usize) -> Value {
+    let receipt = &receipts[position];
+    let first_log_index = first_log_index(receipts, position);
+    json!({
+        "transactionHash": receipt.transaction_hash,
+        "transactionIndex": quantity(receipt.transaction_index),
+        "blockHash": receipt.block_hash,
+        "blockNumber": quantity(receipt.block_number),
+        "from": receipt.from,
+        "to": receipt.to,
+        "contractAddress": receipt.contract_address,
+        "cumulativeGasUsed": quantity(receipt.cumulative_gas_used),
+        "gasUsed": quantity(receipt.gas_used),
+        "effectiveGasPrice": quantity(receipt.effective_gas_price),
+        "logs": (0..receipt.logs.len()).map(|i| log_json(receipt, i, first_log_index + i)).collect::<Vec<_>>(),
+        "logsBloom": receipt.logs_bloom,
+        "status": quantity(u64::from(receipt.status())),
+        "type": quantity(receipt.signed.as_ref().map_or(0, |signed| signed.transaction_type)),
+    })
+}
+
+// Transactions sent unsigned have zero signature fields
+fn transaction_json(chain: &Chain, receipt: &Receipt) -> Value {
+    let sealed = receipt.block_hash.is_some();
+    let mut json = json!({
+        "hash": receipt.transaction_hash,
+        "nonce": quantity(receipt.nonce),
+        "blockHash": receipt.block_hash,
+        "blockNumber": if sealed { quantity(receipt.block_number) } else { Value::Null },
+        "transactionIndex": if sealed { quantity(receipt.transaction_index) } else { Value::Null },
+        "from": receipt.from,
+        "to": receipt.to,
+        "value": quantity(receipt.value),
+        "gas": quantity(receipt.gas_limit),
+        "gasPrice": quantity(receipt.effective_gas_price),
+        "input": data(&receipt.input),
+        "type": "0x0",
+        "chainId": quantity(chain.vicinity.chain_id),
+        "v": "0x0",
+        "r": "0x0",
+        "s": "0x0",
+    });
+    let Some(signed) = &receipt.signed else {
+        return json;
+    };
+    json["type"] = quantity(signed.transaction_type);
+    match signed.chain_id {
+        Some(chain_id) => json["chainId"] = quantity(chain_id),
+        None => if let Some(fields) = json.as_object_mut() {
+            fields.remove("chainId");
+        },
+    }
+    json["v"] = quantity(signed.v);
+    json["r"] = quantity(signed.r);
+    json["s"] = quantity(signed.s);
+    if signed.transaction_type != 0 {
+        json["yParity"] = quantity(signed.v);
+        json["accessList"] = access_list_json(&signed.access_list);
+    }
+    if signed.transaction_type == 2 {
+        json["maxFeePerGas"] = quantity(signed.max_fee_per_gas);
+        json["maxPriorityFeePerGas"] = quantity(signed.max_priority_fee_per_gas);
+    }
+    json
+}
+
+fn access_list_json(access_list: &[(H160, Vec<H256>)]) -> Value {
+    json!(access_list.iter()
+        .map(|(address, keys)| json!({ "address": address, "storageKeys": keys }))
+        .collect::<Vec<_>>())
+}
+
+fn block_json(chain: &Chain, block: &Block, full: bool) -> Value {
+    let receipts = chain.receipts.iter()
+        .filter(|receipt| receipt.block_hash == Some(block.hash))
+        .collect::<Vec<_>>();
+    let mut logs_bloom = Bloom::default();
+    for receipt in &receipts {
+        logs_bloom.accrue_bloom(&receipt.logs_bloom);
+    }
+    let transactions = if full {
+        receipts.iter().map(|receipt| transaction_json(chain, receipt)).collect::<Vec<_>>()
+    } else {
+        block.transactions.iter().map(|hash| json!(hash)).collect()
+    };
+
+    let mut json = json!({
+        "number": quantity(block.number),
+        "hash": block.hash,
+        "parentHash": block.parent_hash,
+        "nonce": "0x0000000000000000",
+        "sha3Uncles": EMPTY_UNCLES_HASH,
+        "logsBloom": logs_bloom,
+        "transactionsRoot": H256::zero(),
+        "stateRoot": H256::zero(),
+        "receiptsRoot": H256::zero(),
+        "miner": block.coinbase,
+        "difficulty": "0x0",
+        "totalDifficulty": "0x0",
+        "extraData": "0x",
+        "size": "0x0",
+        "gasLimit": quantity(block.gas_limit),
+        "gasUsed": quantity(block.gas_used),
+        "timestamp": quantity(block.timestamp),
+        "mixHash": block.prevrandao.unwrap_or_default(),
+        "transactions": transactions,
+        "uncles": [],
+    });
+    if chain.config.has_base_fee {
+        json["baseFeePerGas"] = quantity(block.base_fee);
+    }
+    json
+}
+
+fn find_block(chain: &Chain, number: U256) -> Option<&Block> {
+    // A resumed session doesn't start at block 0
+    let first = chain.blocks.first()?.number;
+    if number < first {
+        return None;
+    }
+    chain.blocks.get((number - first).low_u64() as usize)
+}
+
+// Only the current state is kept, so state queries for older blocks can't be answered
+fn check_latest_state(chain: &Chain, block: Option<&Value>) -> Result<(), RpcError> {
+    let Some(block) = block.filter(|block| !block.is_null()) else {
+        return Ok(());
+    };
+    let number = parse_block_number(chain, block)?;
+    if number < chain.latest_block().number {
+        return Err(RpcError::server(format!("state of block {} is not kept, only the latest state is available", number)));
+    }
+    Ok(())
+}
+
+// Block tags and numbers. The pending block is the one being filled, block queries
+// for it are answered with the latest block since it isn't sealed yet.
+fn parse_block_number(chain: &Chain, value: &Value) -> Result<U256, RpcError> {
+    match value.as_str() {
+        Some("latest") | Some("pending") | Some("safe") | Some("finalized") => Ok(chain.latest_block().number),
+        Some("earliest") => Ok(chain.blocks.first().map_or(U256::zero(), |block| block.number)),
+        _ => parse_quantity(value),
+    }
+}
+
+// A transaction object of eth_call, eth_estimateGas or eth_sendTransaction
+fn parse_transaction(chain: &Chain, value: &Value, default_from: H160) -> Result<Transaction, RpcError> {
+    if !value.is_object() {
+        return Err(RpcError::invalid_params("expected a transaction object"));
+    }
+    let field = |name: &str| value.get(name).filter(|value| !value.is_null());
+
+    // EIP-1559 fees are turned into the price the transaction would effectively pay
+    let gas_price = match (field("gasPrice"), field("maxFeePerGas")) {
+        (Some(gas_price), _) => Some(parse_quantity(gas_price)?),
+        (None, Some(max_fee)) => {
+            let priority_fee = field("maxPriorityFeePerGas").map(parse_quantity).transpose()?.unwrap_or_default();
+            Some(parse_quantity(max_fee)?.min(chain.vicinity.block_base_fee_per_gas + priority_fee))
+        },
+        (None, None) => None,
+    };
+    let access_list = match field("accessList") {
+        Some(Value::Array(entries)) => entries.iter()
+            .map(|entry| {
+                let address = parse_address(entry.get("address").unwrap_or(&Value::Null))?;
+                let keys = match entry.get("storageKeys") {
+                    Some(Value::Array(keys)) => keys.iter().map(parse_hash).collect::<Result<Vec<_>, _>>()?,
+                    _ => Vec::new(),
+                };
+                Ok((address, keys))
+            })
+            .collect::<Result<Vec<_>, RpcError>>()?,
+        Some(_) => return Err(RpcError::invalid_params("accessList must be an array")),
+        None => Vec::new(),
+    };
+
+    Ok(Transaction {
+        caller: field("from").map(parse_address).transpose()?.unwrap_or(default_from),
+        to: field("to").map(parse_address).transpose()?,
+        value: field("value").map(parse_quantity).transpose()?.unwrap_or_default(),
+        // Clients send the calldata as input or, in older versions, as data
+        data: field("input").or_else(|| field("data")).map(parse_hex).transpose()?.unwrap_or_default(),
+        gas_limit: field("gas").map(parse_gas).transpose()?,
+        gas_price,
+        access_list,
+        nonce: field("nonce").map(parse_quantity).transpose()?,
+        hash: None,
+    })
+}
+
+fn required(params: &[Value], index: usize) -> Result<&Value, RpcError> {
+    params.get(index)
+        .filter(|param| !param.is_null())
+        .ok_or_else(|| RpcError::invalid_params(format!("missing parameter {}", index)))
+}
+
+fn parse_hex(value: &Value) -> Result<Vec<u8>, RpcError> {
+    let input = value.as_str().ok_or_else(|| RpcError::invalid_params(format!("expected a hex string, got {}", value)))?;
+    hex::decode(input.strip_prefix("0x").unwrap_or(input))
+        .map_err(|e| RpcError::invalid_params(format!("invalid hex {}: {}", input, e)))
+}
+
+fn parse_address(value: &Value) -> Result<H160, RpcError> {
+    let bytes = parse_hex(value)?;
+    if bytes.len() != 20 {
+        return Err(RpcError::invalid_params(format!("invalid address {}", value)));
+    }
+    Ok(H160::from_slice(&bytes))
+}
+
+fn parse_hash(value: &Value) -> Result<H256, RpcError> {
+    let bytes = parse_hex(value)?;
+    if bytes.len() != 32 {
+        return Err(RpcError::invalid_params(format!("invalid hash {}", value)));
+    }
+    Ok(H256::from_slice(&bytes))
+}
+
+// Gas is counted in 64 bits, larger limits are rejected rather than cut down
+fn parse_gas(value: &Value) -> Result<u64, RpcError> {
+    let gas = parse_quantity(value)?;
+    u64::try_from(gas).map_err(|_| RpcError::invalid_params(format!("gas limit {} is too large", gas)))
+}
+
+// Quantities are hex strings without leading zeros, plain numbers are accepted too
+fn parse_quantity(value: &Value) -> Result<U256, RpcError> {
+    match value {
+        Value::Number(number) => number.as_u64()
+            .map(U256::from)
+            .ok_or_else(|| RpcError::invalid_params(format!("invalid quantity {}", number))),
+        Value::String(s) => {
+            let digits = s.strip_prefix("0x")
+                .ok_or_else(|| RpcError::invalid_params(format!("invalid quantity {}, expected 0x followed by hex digits", s)))?;
+            U256::from_str_radix(if digits.is_empty() { "0" } else { digits }, 16)
+                .map_err(|_| RpcError::invalid_params(format!("invalid quantity {}", s)))
+        },
+        other => Err(RpcError::invalid_params(format!("invalid quantity {}", other))),
+    }
+}
+
+fn quantity(value: impl Into<U256>) -> Value {
+    json!(format!("{:#x}", value.into()))
+}
+
+fn data(bytes: &[u8]) -> Value {
+    json!(format!("0x{}", hex::encode(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::tests::chain_with;
+
+    const SENDER: &str = "0x1111111111111111111111111111111111111111";
+
+    fn call(chain: &mut Chain, method: &str, params: Value) -> Value {
+        let contracts_data = ContractsData::new();
+        let sender = SENDER.parse().unwrap();
+        let mut node = Node { chain, contracts_data: &contracts_data, accounts: vec![sender], deployer: sender, state_path: None };
+        let params = params.as_array().cloned().unwrap_or_default();
+        match dispatch(&mut node, method, &params) {
+            Ok(result) => result,
+            Err(error) => panic!("{} failed: {}", method, error.message),
+        }
+    }
+
+    #[test]
+    fn answers_chain_and_state_queries() {
+        let mut chain = chain_with(SENDER.parse().unwrap(), U256::exp10(18));
+        assert_eq!(call(&mut chain, "eth_chainId", json!([])), json!("0x7a69"));
+        assert_eq!(call(&mut chain, "eth_getBalance", json!([SENDER, "latest"])), json!("0xde0b6b3a7640000"));
+        assert_eq!(call(&mut chain, "eth_getBalance", json!(["0x2222222222222222222222222222222222222222", "latest"])), json!("0x0"));
+    }
+
+    #[test]
+    fn estimates_a_plain_transfer_at_21000() {
+        let mut chain = chain_with(SENDER.parse().unwrap(), U256::exp10(18));
+        let transfer = json!([{ "from": SENDER, "to": "0x2222222222222222222222222222222222222222", "value": "0x1" }]);
+        assert_eq!(call(&mut chain, "eth_estimateGas", transfer), json!("0x5208"));
+    }
+
+    #[test]
+    fn reports_errors_as_json_rpc_error_objects() {
+        let mut chain = chain_with(SENDER.parse().unwrap(), U256::exp10(18));
+        let contracts_data = ContractsData::new();
+        let mut node = Node { chain: &mut chain, contracts_data: &contracts_data, accounts: Vec::new(), deployer: H160::zero(), state_path: None };
+
+        let response = handle_body(&mut node, r#"{ "jsonrpc": "2.0", "id": 7, "method": "eth_mine_everything", "params": [] }"#);
+        assert_eq!(response, json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "error": { "code": -32601, "message": "Method eth_mine_everything is not supported" },
+        }));
+
+        let too_much_gas = r#"{ "jsonrpc": "2.0", "id": 8, "method": "eth_estimateGas", "params": [{ "gas": "0x10000000000000000" }] }"#;
+        assert_eq!(handle_body(&mut node, too_much_gas)["error"]["code"], json!(-32602));
+    }
+}
@@ -0,0 +1,356 @@
+use ethabi::param_type::ParamType;
+use ethereum_types::H160;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::Value;
+
+use std::fs;
+use std::io;
+
+use crate::abi_format::format_token;
+use crate::abi_parse::parse_value;
+use crate::access_list::AccessList;
+use crate::block;
+use crate::revert;
+use crate::chain::Chain;
+use crate::{abi_parse, access_list, call_contract_function, deploy_contract, encode_function_args, parse_recipient, parse_sender, Account, ContractsData};
+
+// A scenario file describes deployments followed by calls and what they should return, e.g.
+//
+// {
+//   "deploy": [{ "contract": "HelloWorld", "args": ["hi", 1, "0x00..01", true, "0x"] }],
+//   "calls": [
+//     { "contract": "HelloWorld", "function": "updateNumber", "args": [42] },
+//     { "contract": "HelloWorld", "function": "number", "expect": [42] },
+//     { "warp": "+86400", "mine": 1 },
+//     { "contract": "HelloWorld", "function": "updateNumber", "args": [-1], "expect_revert": "" }
+//   ]
+// }
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    #[serde(default)]
+    deploy: Vec<DeployStep>,
+    #[serde(default)]
+    calls: Vec<Step>,
+}
+
+enum Step {
+    Call(CallStep),
+    Block(BlockStep),
+}
+
+// Steps naming a contract are calls, the others change blocks. Picked by hand rather
+// than with an untagged enum so that mistakes are reported field by field.
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let step = if value.get("contract").is_some() {
+            CallStep::deserialize(value).map(Step::Call)
+        } else {
+            BlockStep::deserialize(value).map(Step::Block)
+        };
+        step.map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeployStep {
+    contract: String,
+    #[serde(default)]
+    args: Vec<Value>,
+    // Sender address or number of one of the funded accounts, defaults to the deployer
+    from: Option<Value>,
+    // Wei sent with the deployment, accepts units like "1 ether"
+    value: Option<String>,
+    // Gas limit and price of the transaction, the chain's defaults when not set
+    gas: Option<u64>,
+    gas_price: Option<String>,
+    // EIP-2930 addresses and storage slots warmed up front, from Berlin on
+    #[serde(default)]
+    access_list: Vec<AccessListEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallStep {
+    contract: String,
+    function: String,
+    #[serde(default)]
+    args: Vec<Value>,
+    from: Option<Value>,
+    value: Option<String>,
+    gas: Option<u64>,
+    gas_price: Option<String>,
+    #[serde(default)]
+    access_list: Vec<AccessListEntry>,
+    // Expected return values, compared after parsing them as the function's output types
+    expect: Option<Vec<Value>>,
+    // Expect the call to fail, with the failure description containing this text ("" for any failure)
+    expect_revert: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessListEntry {
+    // Contract name, address or account number
+    address: Value,
+    // Slot numbers or 32 byte hex keys
+    #[serde(default)]
+    storage_keys: Vec<Value>,
+}
+
+// Changes the pending block, in the order of the fields, and then mines
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockStep {
+    automine: Option<bool>,
+    // Address or account number
+    coinbase: Option<Value>,
+    base_fee: Option<String>,
+    prevrandao: Option<String>,
+    // Timestamp in seconds, or an offset like "+3600"
+    warp: Option<Value>,
+    // Number of blocks to seal
+    mine: Option<u64>,
+}
+
+// Runs every step of the scenario and prints a pass/fail line for each.
+// Returns whether all steps passed.
+pub fn run_scenario(
+    path: &str,
+    chain: &mut Chain,
+    contracts_data: &mut ContractsData,
+    deployer_address: H160,
+    accounts: &[Account],
+) -> Result<bool, io::Error> {
+    let contents = fs::read_to_string(path)?;
+    let scenario: Scenario = serde_json::from_str(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid scenario {}: {}", path, e)))?;
+
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for (i, step) in scenario.deploy.iter().enumerate() {
+        println!("\n*** Deploy step {}: {} ***", i + 1, step.contract);
+        match run_deploy_step(chain, contracts_data, step, deployer_address, accounts) {
+            Ok(()) => {
+                println!("PASS deploy {}", step.contract);
+                passed += 1;
+            },
+            Err(e) => {
+                println!("FAIL deploy {}: {}", step.contract, e);
+                failed += 1;
+            },
+        }
+    }
+
+    for (i, step) in scenario.calls.iter().enumerate() {
+        let step = match step {
+            Step::Call(step) => step,
+            Step::Block(step) => {
+                println!("\n*** Block step {} ***", i + 1);
+                match run_block_step(chain, step, accounts) {
+                    Ok(()) => {
+                        println!("PASS block step");
+                        passed += 1;
+                    },
+                    Err(e) => {
+                        println!("FAIL block step: {}", e);
+                        failed += 1;
+                    },
+                }
+                continue;
+            },
+        };
+        println!("\n*** Call step {}: {}.{} ***", i + 1, step.contract, step.function);
+        match run_call_step(chain, contracts_data, step, deployer_address, accounts) {
+            Ok(()) => {
+                println!("PASS {}.{}", step.contract, step.function);
+                passed += 1;
+            },
+            Err(e) => {
+                println!("FAIL {}.{}: {}", step.contract, step.function, e);
+                failed += 1;
+            },
+        }
+    }
+
+    println!("\n*** Scenario {}: {} passed, {} failed ***", path, passed, failed);
+    Ok(failed == 0)
+}
+
+fn run_deploy_step(
+    chain: &mut Chain,
+    contracts_data: &mut ContractsData,
+    step: &DeployStep,
+    deployer_address: H160,
+    accounts: &[Account],
+) -> Result<(), io::Error> {
+    let contract_data = contracts_data.get(&step.contract)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown contract {}", step.contract)))?;
+
+    let params = contract_data.abi.constructor()
+        .map(|constructor| constructor.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let encoded_args = encode_function_args(&params, to_literals(&params, &step.args)?)?;
+
+    let from = step_sender(step.from.as_ref(), deployer_address, accounts)?;
+    let value = parse_value(step.value.as_deref())?;
+    with_gas_settings(chain, step.gas, step.gas_price.as_deref(), |chain| {
+        // Set here, so a step with a bad gas price doesn't leave its list to the next transaction
+        chain.access_list = parse_access_list(&step.access_list, contracts_data, accounts)?;
+        deploy_contract(chain, contracts_data, &step.contract, encoded_args, from, value)
+    })?;
+    Ok(())
+}
+
+fn run_call_step(
+    chain: &mut Chain,
+    contracts_data: &ContractsData,
+    step: &CallStep,
+    deployer_address: H160,
+    accounts: &[Account],
+) -> Result<(), io::Error> {
+    let function = contracts_data.get(&step.contract)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown contract {}", step.contract)))?
+        .abi.function(&step.function)
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, format!("Unknown function {}", step.function)))?;
+
+    let params = function.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
+    let encoded_args = encode_function_args(&params, to_literals(&params, &step.args)?)?;
+
+    let from = step_sender(step.from.as_ref(), deployer_address, accounts)?;
+    let value = parse_value(step.value.as_deref())?;
+    let result = with_gas_settings(chain, step.gas, step.gas_price.as_deref(), |chain| {
+        chain.access_list = parse_access_list(&step.access_list, contracts_data, accounts)?;
+        call_contract_function(chain, contracts_data, &step.contract, &step.function, encoded_args, from, value)
+    });
+
+    match (result, &step.expect_revert) {
+        (Ok(_), Some(_)) => Err(io::Error::other("expected the call to revert, but it succeeded")),
+        // Only a call that ran and reverted counts, not one the chain refused to run
+        (Err(e), Some(expected)) if revert::is_revert(&e) => {
+            if e.to_string().contains(expected.as_str()) {
+                println!("Reverted as expected: {}", e);
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("expected a revert containing {:?}, got: {}", expected, e)))
+            }
+        },
+        (Err(e), _) => Err(e),
+        (Ok(output), None) => {
+            let Some(expected) = &step.expect else {
+                return Ok(());
+            };
+            let output_types = function.outputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>();
+            let expected_tokens = to_literals(&output_types, expected)?.iter()
+                .zip(output_types.iter())
+                .map(|(literal, param)| abi_parse::parse_token(param, literal))
+                .collect::<Result<Vec<_>, _>>()?;
+            if expected_tokens == output {
+                Ok(())
+            } else {
+                let render = |tokens: &[ethabi::Token]| tokens.iter().map(format_token).collect::<Vec<_>>().join(", ");
+                Err(io::Error::other(format!("expected [{}], got [{}]", render(&expected_tokens), render(&output))))
+            }
+        },
+    }
+}
+
+fn run_block_step(chain: &mut Chain, step: &BlockStep, accounts: &[Account]) -> Result<(), io::Error> {
+    if let Some(automine) = step.automine {
+        chain.automine = automine;
+    }
+    if let Some(coinbase) = &step.coinbase {
+        chain.vicinity.block_coinbase = parse_sender(&to_literal(coinbase), accounts)?;
+    }
+    if let Some(base_fee) = &step.base_fee {
+        chain.vicinity.block_base_fee_per_gas = parse_value(Some(base_fee))?;
+    }
+    if let Some(prevrandao) = &step.prevrandao {
+        chain.vicinity.block_randomness = Some(block::parse_prevrandao(prevrandao)?);
+    }
+    if let Some(warp) = &step.warp {
+        chain.warp(block::parse_timestamp(&to_literal(warp), chain.vicinity.block_timestamp)?)?;
+    }
+    if let Some(count) = step.mine {
+        chain.mine(count);
+    }
+    let latest = chain.latest_block();
+    println!("Latest block {} at timestamp {}, pending block {} at timestamp {}",
+        latest.number, latest.timestamp, chain.vicinity.block_number, chain.vicinity.block_timestamp);
+    Ok(())
+}
+
+// Turns JSON arguments into the literal syntax the interactive prompts accept,
+// so `[1, 2]` and `"[1, 2]"` mean the same thing.
+fn to_literals(params: &[ParamType], args: &[Value]) -> Result<Vec<String>, io::Error> {
+    if params.len() != args.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("expected {} arguments, got {}", params.len(), args.len())));
+    }
+    Ok(args.iter().map(|arg| match arg {
+        // Top level strings are taken as typed, like at the prompt
+        Value::String(s) => s.clone(),
+        other => to_literal(other),
+    }).collect())
+}
+
+fn to_literal(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        // Nested strings only need quotes when they would confuse the list parser
+        Value::String(s) if s.is_empty() || s.contains(|c: char| ",[]()\"\\".contains(c) || c.is_whitespace()) => format!("{:?}", s),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => format!("[{}]", items.iter().map(to_literal).collect::<Vec<_>>().join(", ")),
+        // Objects are treated as tuples, in the order their fields are written
+        Value::Object(fields) => format!("({})", fields.values().map(to_literal).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+fn step_sender(from: Option<&Value>, default: H160, accounts: &[Account]) -> Result<H160, io::Error> {
+    match from {
+        Some(from) => parse_sender(&to_literal(from), accounts),
+        None => Ok(default),
+    }
+}
+
+// Runs a step with its own gas limit and price in place of the chain's defaults
+fn with_gas_settings<T>(
+    chain: &mut Chain,
+    gas: Option<u64>,
+    gas_price: Option<&str>,
+    step: impl FnOnce(&mut Chain) -> Result<T, io::Error>,
+) -> Result<T, io::Error> {
+    // Parsed before touching the chain, so a bad price leaves the defaults alone
+    let gas_price = match gas_price {
+        Some(gas_price) => parse_value(Some(gas_price))?,
+        None => chain.gas_price,
+    };
+    let defaults = (chain.gas_limit, chain.gas_price);
+    chain.gas_limit = gas.unwrap_or(chain.gas_limit);
+    chain.gas_price = gas_price;
+    let result = step(chain);
+    (chain.gas_limit, chain.gas_price) = defaults;
+    result
+}
+
+fn parse_access_list(
+    entries: &[AccessListEntry],
+    contracts_data: &ContractsData,
+    accounts: &[Account],
+) -> Result<AccessList, io::Error> {
+    let mut list = Vec::new();
+    for entry in entries {
+        let address = parse_recipient(&to_literal(&entry.address), contracts_data, accounts)?;
+        let keys = entry.storage_keys.iter()
+            .map(|key| access_list::parse_storage_key(&to_literal(key)))
+            .collect::<Result<Vec<_>, _>>()?;
+        access_list::add(&mut list, address, keys);
+    }
+    Ok(list)
+}
@@ -0,0 +1,99 @@
+use ethereum_types::{H160, H256, U256};
+use evm::backend::MemoryAccount;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::chain::Chain;
+use crate::ContractsData;
+
+// Everything needed to resume a session: the world state, who deployed the
+// contracts and where each of them lives.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    pub deployer: H160,
+    pub accounts: BTreeMap<H160, AccountState>,
+    #[serde(default)]
+    pub contracts: BTreeMap<String, H160>,
+    // The latest block, so a resumed session continues at the same height and time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<BlockHead>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockHead {
+    pub number: U256,
+    pub timestamp: U256,
+    pub base_fee: U256,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountState {
+    pub nonce: U256,
+    pub balance: U256,
+    // Hex encoded runtime bytecode, empty for externally owned accounts
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl Snapshot {
+    pub fn take(chain: &Chain, deployer: H160, contracts_data: &ContractsData) -> Self {
+        let accounts = chain.state.iter()
+            .map(|(address, account)| (*address, AccountState {
+                nonce: account.nonce,
+                balance: account.balance,
+                code: if account.code.is_empty() { String::new() } else { format!("0x{}", hex::encode(&account.code)) },
+                storage: account.storage.clone(),
+            }))
+            .collect();
+        let contracts = contracts_data.iter()
+            .filter_map(|(name, contract_data)| contract_data.address.map(|address| (name.clone(), address)))
+            .collect();
+
+        let latest = chain.latest_block();
+        let block = Some(BlockHead {
+            number: latest.number,
+            timestamp: latest.timestamp,
+            base_fee: latest.base_fee,
+        });
+
+        Snapshot { deployer, accounts, contracts, block }
+    }
+
+    // Converts the saved accounts back into the genesis of a new backend
+    pub fn genesis(&self) -> Result<BTreeMap<H160, MemoryAccount>, io::Error> {
+        self.accounts.iter()
+            .map(|(address, account)| {
+                let code = hex::decode(account.code.trim_start_matches("0x"))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid code of {:?}: {}", address, e)))?;
+                Ok((*address, MemoryAccount {
+                    nonce: account.nonce,
+                    balance: account.balance,
+                    storage: account.storage.clone(),
+                    code,
+                }))
+            })
+            .collect()
+    }
+}
+
+pub fn load(path: &str) -> Result<Snapshot, io::Error> {
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid state file {}: {}", path, e)))
+}
+
+pub fn save(path: &str, snapshot: &Snapshot) -> Result<(), io::Error> {
+    let contents = serde_json::to_string_pretty(snapshot).map_err(io::Error::other)?;
+    // Write to a temporary file first so an interrupted save keeps the previous snapshot intact
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
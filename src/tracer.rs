@@ -0,0 +1,383 @@
+use ethereum_types::{H160, H256, U256};
+use evm::gasometer::tracing as gasometer_tracing;
+use evm::tracing as executor_tracing;
+use evm::{Capture, ExitReason};
+use evm_runtime::tracing as runtime_tracing;
+use serde_json::{json, Map, Value};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+use crate::call_tree::{CallRecorder, Frame};
+
+// What the chain does with the steps of each transaction
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tracing {
+    Off,
+    // Keep the trace of the last transaction, for dumping it later
+    Record,
+    // Keep it and print it after every transaction
+    Print,
+}
+
+// One executed opcode, with the machine state from before it ran
+pub struct Step {
+    pub pc: usize,
+    pub opcode: u8,
+    // 1 for the transaction itself, +1 for every nested call or create
+    pub depth: usize,
+    // Call frame the step ran in, used to compare memory between steps of the same frame
+    frame: usize,
+    pub gas: u64,
+    charged: bool,
+    // Gas charged for the opcode itself, excluding gas forwarded to a callee
+    pub gas_cost: u64,
+    pub stack: Vec<H256>,
+    pub memory: Vec<u8>,
+    pub storage_reads: Vec<(H256, H256)>,
+    pub storage_writes: Vec<(H256, H256)>,
+    // Every slot of the executing contract touched so far, set on SLOAD and SSTORE like geth does
+    pub storage: Option<BTreeMap<H256, H256>>,
+    pub error: Option<String>,
+}
+
+pub struct Trace {
+    pub steps: Vec<Step>,
+    pub call_tree: Option<Frame>,
+    pub gas_used: u64,
+    pub failed: bool,
+    pub return_value: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    // Opcode steps are only kept when asked for, the call tree is always built
+    record_steps: bool,
+    steps: Vec<Step>,
+    calls: CallRecorder,
+    depth: usize,
+    frames: Vec<usize>,
+    next_frame: usize,
+    // Gas events belong to the last step until the next step or call frame change
+    open: bool,
+    storage: HashMap<H160, BTreeMap<H256, H256>>,
+}
+
+impl Recorder {
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.next_frame += 1;
+        self.frames.push(self.next_frame);
+        self.open = false;
+    }
+
+    fn exit(&mut self, reason: &ExitReason) {
+        if self.open {
+            if let (Some(step), ExitReason::Error(e)) = (self.steps.last_mut(), reason) {
+                step.error.get_or_insert_with(|| format!("{:?}", e));
+            }
+        }
+        self.depth = self.depth.saturating_sub(1);
+        self.frames.pop();
+        self.open = false;
+    }
+
+    fn record_storage(&mut self, address: H160, index: H256, value: H256, write: bool) {
+        let storage = self.storage.entry(address).or_default();
+        storage.insert(index, value);
+        let storage = storage.clone();
+        if let Some(step) = self.steps.last_mut() {
+            if write {
+                step.storage_writes.push((index, value));
+            } else {
+                step.storage_reads.push((index, value));
+            }
+            step.storage = Some(storage);
+        }
+    }
+}
+
+struct RuntimeListener(Rc<RefCell<Recorder>>);
+struct GasListener(Rc<RefCell<Recorder>>);
+struct ExecutorListener(Rc<RefCell<Recorder>>);
+
+impl runtime_tracing::EventListener for RuntimeListener {
+    fn event(&mut self, event: runtime_tracing::Event<'_>) {
+        let mut recorder = self.0.borrow_mut();
+        match event {
+            runtime_tracing::Event::Step { .. } if !recorder.record_steps => {},
+            runtime_tracing::Event::Step { opcode, position, stack, memory, .. } => {
+                let depth = recorder.depth.max(1);
+                let frame = recorder.frames.last().copied().unwrap_or(0);
+                recorder.steps.push(Step {
+                    pc: *position.as_ref().unwrap_or(&0),
+                    opcode: opcode.as_u8(),
+                    depth,
+                    frame,
+                    gas: 0,
+                    charged: false,
+                    gas_cost: 0,
+                    stack: stack.data().clone(),
+                    memory: memory.data().clone(),
+                    storage_reads: Vec::new(),
+                    storage_writes: Vec::new(),
+                    storage: None,
+                    error: None,
+                });
+                recorder.open = true;
+            },
+            runtime_tracing::Event::StepResult { result, .. } => {
+                if let Err(Capture::Exit(ExitReason::Error(e))) = result {
+                    if let Some(step) = recorder.steps.last_mut() {
+                        step.error = Some(format!("{:?}", e));
+                    }
+                }
+            },
+            runtime_tracing::Event::SLoad { address, index, value } => recorder.record_storage(address, index, value, false),
+            runtime_tracing::Event::SStore { address, index, value } => recorder.record_storage(address, index, value, true),
+        }
+    }
+}
+
+impl gasometer_tracing::EventListener for GasListener {
+    fn event(&mut self, event: gasometer_tracing::Event) {
+        let mut recorder = self.0.borrow_mut();
+        match event {
+            gasometer_tracing::Event::RecordCost { cost, .. } => recorder.calls.record_cost(cost),
+            gasometer_tracing::Event::RecordStipend { stipend, .. } => recorder.calls.record_stipend(stipend),
+            _ => {},
+        }
+        if !recorder.open {
+            return;
+        }
+        let (cost, snapshot) = match event {
+            gasometer_tracing::Event::RecordCost { cost, snapshot } => (cost, snapshot),
+            gasometer_tracing::Event::RecordDynamicCost { gas_cost, memory_gas, snapshot, .. } => {
+                let memory_cost = snapshot.map_or(0, |snapshot| memory_gas.saturating_sub(snapshot.memory_gas));
+                (gas_cost + memory_cost, snapshot)
+            },
+            _ => return,
+        };
+        if let Some(step) = recorder.steps.last_mut() {
+            // The first charge of a step sees the gas that was left before it
+            if !step.charged {
+                step.gas = snapshot.map_or(0, |snapshot| snapshot.gas());
+                step.charged = true;
+            }
+            step.gas_cost += cost;
+        }
+    }
+}
+
+impl executor_tracing::EventListener for ExecutorListener {
+    fn event(&mut self, event: executor_tracing::Event<'_>) {
+        let mut recorder = self.0.borrow_mut();
+        match event {
+            executor_tracing::Event::Call { code_address, transfer, input, is_static, context, .. } => {
+                recorder.enter();
+                recorder.calls.enter_call(code_address, context, input, transfer.is_some(), is_static);
+            },
+            executor_tracing::Event::Create { caller, address, scheme, value, init_code, .. } => {
+                recorder.enter();
+                recorder.calls.enter_create(caller, address, scheme, value, init_code);
+            },
+            executor_tracing::Event::Exit { reason, return_value } => {
+                recorder.exit(reason);
+                recorder.calls.exit(reason, return_value);
+            },
+            _ => {},
+        }
+    }
+}
+
+// Runs `f` with listeners attached to the EVM and returns its result with the call
+// tree and, if `record_steps` is set, every step it executed
+pub fn record<R, F: FnOnce() -> R>(record_steps: bool, f: F) -> (R, Vec<Step>, Option<Frame>) {
+    let recorder = Rc::new(RefCell::new(Recorder { record_steps, ..Default::default() }));
+    let mut runtime_listener = RuntimeListener(recorder.clone());
+    let mut gas_listener = GasListener(recorder.clone());
+    let mut executor_listener = ExecutorListener(recorder.clone());
+
+    let result = executor_tracing::using(&mut executor_listener, || {
+        gasometer_tracing::using(&mut gas_listener, || {
+            runtime_tracing::using(&mut runtime_listener, f)
+        })
+    });
+
+    let mut recorder = recorder.borrow_mut();
+    (result, std::mem::take(&mut recorder.steps), recorder.calls.root.take())
+}
+
+pub fn print_trace(trace: &Trace) {
+    println!("Trace: {} steps, {} gas used{}", trace.steps.len(), trace.gas_used, if trace.failed { ", failed" } else { "" });
+    println!("  {:>5} {:>6}  {:<14} {:>10} {:>8}  stack (top first)", "depth", "pc", "opcode", "gas", "cost");
+
+    let memory_changes = memory_changes(&trace.steps);
+    for (i, step) in trace.steps.iter().enumerate() {
+        let stack = step.stack.iter().rev()
+            .map(|word| format!("{:#x}", U256::from_big_endian(word.as_bytes())))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("  {:>5} {:>6}  {:<14} {:>10} {:>8}  [{}]", step.depth, step.pc, opcode_name(step.opcode), step.gas, step.gas_cost, stack);
+
+        for (index, value) in &step.storage_reads {
+            println!("  {:>40}sload  {:?} => {:?}", "", index, value);
+        }
+        for (index, value) in &step.storage_writes {
+            println!("  {:>40}sstore {:?} := {:?}", "", index, value);
+        }
+        for (offset, word) in &memory_changes[i] {
+            println!("  {:>40}memory [{:#x}] = 0x{}", "", offset, hex::encode(word));
+        }
+        if let Some(error) = &step.error {
+            println!("  {:>40}error: {}", "", error);
+        }
+    }
+}
+
+// Words of memory each step changed, found by comparing it with the next step of the same frame
+fn memory_changes(steps: &[Step]) -> Vec<Vec<(usize, Vec<u8>)>> {
+    let mut changes = vec![Vec::new(); steps.len()];
+    let mut previous: HashMap<usize, usize> = HashMap::new();
+    for (i, step) in steps.iter().enumerate() {
+        if let Some(before) = previous.insert(step.frame, i) {
+            let old = &steps[before].memory;
+            for (word, chunk) in step.memory.chunks(32).enumerate() {
+                let offset = word * 32;
+                if old.get(offset..offset + chunk.len()) != Some(chunk) {
+                    changes[before].push((offset, chunk.to_vec()));
+                }
+            }
+        }
+    }
+    changes
+}
+
+// The trace in the format of geth's debug_traceTransaction with the default struct logger
+pub fn struct_logs_json(trace: &Trace) -> Value {
+    let struct_logs = trace.steps.iter().map(|step| {
+        let mut log = Map::new();
+        log.insert("pc".into(), json!(step.pc));
+        log.insert("op".into(), json!(opcode_name(step.opcode)));
+        log.insert("gas".into(), json!(step.gas));
+        log.insert("gasCost".into(), json!(step.gas_cost));
+        log.insert("depth".into(), json!(step.depth));
+        log.insert("stack".into(), json!(step.stack.iter()
+            .map(|word| format!("{:#x}", U256::from_big_endian(word.as_bytes())))
+            .collect::<Vec<_>>()));
+        if !step.memory.is_empty() {
+            log.insert("memory".into(), json!(step.memory.chunks(32).map(hex::encode).collect::<Vec<_>>()));
+        }
+        if let Some(storage) = &step.storage {
+            log.insert("storage".into(), Value::Object(storage.iter()
+                .map(|(index, value)| (hex::encode(index), json!(hex::encode(value))))
+                .collect()));
+        }
+        if let Some(error) = &step.error {
+            log.insert("error".into(), json!(error));
+        }
+        Value::Object(log)
+    }).collect::<Vec<_>>();
+
+    json!({
+        "gas": trace.gas_used,
+        "failed": trace.failed,
+        "returnValue": hex::encode(&trace.return_value),
+        "structLogs": struct_logs,
+    })
+}
+
+pub fn write_struct_logs(path: &str, trace: &Trace) -> Result<(), io::Error> {
+    let contents = serde_json::to_string_pretty(&struct_logs_json(trace)).map_err(io::Error::other)?;
+    fs::write(path, contents)
+}
+
+// Opcode mnemonics as geth prints them
+pub fn opcode_name(opcode: u8) -> String {
+    let name = match opcode {
+        0x00 => "STOP",
+        0x01 => "ADD",
+        0x02 => "MUL",
+        0x03 => "SUB",
+        0x04 => "DIV",
+        0x05 => "SDIV",
+        0x06 => "MOD",
+        0x07 => "SMOD",
+        0x08 => "ADDMOD",
+        0x09 => "MULMOD",
+        0x0a => "EXP",
+        0x0b => "SIGNEXTEND",
+        0x10 => "LT",
+        0x11 => "GT",
+        0x12 => "SLT",
+        0x13 => "SGT",
+        0x14 => "EQ",
+        0x15 => "ISZERO",
+        0x16 => "AND",
+        0x17 => "OR",
+        0x18 => "XOR",
+        0x19 => "NOT",
+        0x1a => "BYTE",
+        0x1b => "SHL",
+        0x1c => "SHR",
+        0x1d => "SAR",
+        0x20 => "KECCAK256",
+        0x30 => "ADDRESS",
+        0x31 => "BALANCE",
+        0x32 => "ORIGIN",
+        0x33 => "CALLER",
+        0x34 => "CALLVALUE",
+        0x35 => "CALLDATALOAD",
+        0x36 => "CALLDATASIZE",
+        0x37 => "CALLDATACOPY",
+        0x38 => "CODESIZE",
+        0x39 => "CODECOPY",
+        0x3a => "GASPRICE",
+        0x3b => "EXTCODESIZE",
+        0x3c => "EXTCODECOPY",
+        0x3d => "RETURNDATASIZE",
+        0x3e => "RETURNDATACOPY",
+        0x3f => "EXTCODEHASH",
+        0x40 => "BLOCKHASH",
+        0x41 => "COINBASE",
+        0x42 => "TIMESTAMP",
+        0x43 => "NUMBER",
+        0x44 => "DIFFICULTY",
+        0x45 => "GASLIMIT",
+        0x46 => "CHAINID",
+        0x47 => "SELFBALANCE",
+        0x48 => "BASEFEE",
+        0x50 => "POP",
+        0x51 => "MLOAD",
+        0x52 => "MSTORE",
+        0x53 => "MSTORE8",
+        0x54 => "SLOAD",
+        0x55 => "SSTORE",
+        0x56 => "JUMP",
+        0x57 => "JUMPI",
+        0x58 => "PC",
+        0x59 => "MSIZE",
+        0x5a => "GAS",
+        0x5b => "JUMPDEST",
+        0x5f => "PUSH0",
+        0x60..=0x7f => return format!("PUSH{}", opcode - 0x5f),
+        0x80..=0x8f => return format!("DUP{}", opcode - 0x7f),
+        0x90..=0x9f => return format!("SWAP{}", opcode - 0x8f),
+        0xa0..=0xa4 => return format!("LOG{}", opcode - 0xa0),
+        0xf0 => "CREATE",
+        0xf1 => "CALL",
+        0xf2 => "CALLCODE",
+        0xf3 => "RETURN",
+        0xf4 => "DELEGATECALL",
+        0xf5 => "CREATE2",
+        0xfa => "STATICCALL",
+        0xfd => "REVERT",
+        0xfe => "INVALID",
+        0xff => "SELFDESTRUCT",
+        _ => return format!("opcode {:#04x}", opcode),
+    };
+    name.to_string()
+}